@@ -2,8 +2,13 @@ use std::fs;
 use std::io;
 use std::env;
 use std::io::Read;
+use std::sync::Arc;
 
 use ez_bencoding::BdecodeNode;
+#[cfg(feature = "mmap")]
+use ez_bencoding::Mmap;
+use ez_bencoding::magnet::Magnet;
+use ez_bencoding::torrent::{to_hex, verify, InfoHashes, Metainfo, TorrentBuilder, VerifyStatus};
 
 fn main() -> io::Result<()> {
     // 获取命令行参数
@@ -11,20 +16,203 @@ fn main() -> io::Result<()> {
 
     // 检查是否提供了文件路径作为参数
     if args.len() < 2 {
-        eprintln!("Usage: {:?} <file_path>", args);
+        eprintln!("Usage: {} <file_path>", args[0]);
+        eprintln!("       {} magnet <torrent_file | magnet_uri>", args[0]);
+        eprintln!(
+            "       {} create <path> <output> [-a announce]... [-c comment] [-p piece_length] [-s source] [--private]",
+            args[0]
+        );
+        eprintln!("       {} verify <torrent_file> <dir>", args[0]);
         return Err(io::Error::new(io::ErrorKind::InvalidInput, "No file path provided"));
     }
 
-    // 获取文件路径
-    let file_path = &args[1];
+    match args[1].as_str() {
+        "magnet" if args.len() > 2 => magnet(&args[2]),
+        "create" if args.len() > 3 => create(&args[2], &args[3], &args[4..]),
+        "verify" if args.len() > 3 => verify_data(&args[2], &args[3]),
+        file_path => dump(file_path),
+    }
+}
+
+/// 超过该大小的文件使用内存映射解析，不再读入内存
+#[cfg(feature = "mmap")]
+const MMAP_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// 文件内容，小文件读入 Vec<u8>, 大文件使用只读的内存映射
+///
+/// 数据通过 Arc 共享，解析失败时仍然可以用同一份数据输出出错位置附近的字节
+#[derive(Clone)]
+enum FileBuffer {
+    Heap(Arc<Vec<u8>>),
+    #[cfg(feature = "mmap")]
+    Mapped(Arc<Mmap>),
+}
+
+impl AsRef<[u8]> for FileBuffer {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            FileBuffer::Heap(buffer) => buffer,
+            #[cfg(feature = "mmap")]
+            FileBuffer::Mapped(mmap) => mmap,
+        }
+    }
+}
+
+/// 读取文件并解析，解析失败时输出错误报告并退出
+fn read_file(file_path: &str) -> BdecodeNode<FileBuffer> {
     let mut file = fs::File::open(file_path).expect("Failed to open file");
 
+    #[cfg(feature = "mmap")]
+    if file.metadata().expect("Failed to read metadata").len() > MMAP_THRESHOLD {
+        // 只读映射，cat_torrent 运行期间不会修改文件
+        let mmap = unsafe { Mmap::map(&file) }.expect("Failed to map file");
+        return parse_or_exit(file_path, FileBuffer::Mapped(Arc::new(mmap)));
+    }
+
     // 读取文件内容到 Vec<u8>
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer).expect("Failed to read file");
 
-    let root_node = BdecodeNode::parse_buffer(buffer.into()).unwrap();
-    println!("{}", &root_node.to_json_pretty());
+    parse_or_exit(file_path, FileBuffer::Heap(Arc::new(buffer)))
+}
+
+/// 解析 buffer, 失败时输出错误信息、出错位置的路径和附近的字节，然后退出
+fn parse_or_exit(file_path: &str, buffer: FileBuffer) -> BdecodeNode<FileBuffer> {
+    match BdecodeNode::parse_from(buffer.clone(), None, None) {
+        Ok(node) => node,
+        Err(e) => {
+            eprint!("{}: {}", file_path, e.render(buffer.as_ref()));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 以 json 格式输出文件内容，如果是 torrent 文件，则输出 info-hash
+fn dump(file_path: &str) -> io::Result<()> {
+    let root_node = read_file(file_path);
+    println!("{}", root_node.to_json_pretty());
+
+    if let Ok(hashes) = InfoHashes::from_node(&root_node) {
+        let (is_v1, is_v2) = match Metainfo::from_node(&root_node) {
+            Ok(metainfo) => (metainfo.info.is_v1(), metainfo.info.is_v2()),
+            Err(_) => (true, true),
+        };
+
+        if is_v1 {
+            println!("info hash v1: {}", to_hex(&hashes.v1));
+        }
+        if is_v2 {
+            println!("info hash v2: {}", to_hex(&hashes.v2));
+            println!("info hash v2 (truncated): {}", to_hex(&hashes.v2_truncated()));
+        }
+    }
+
+    Ok(())
+}
+
+/// 由 torrent 文件生成 magnet 链接，或者解析 magnet 链接
+fn magnet(input: &str) -> io::Result<()> {
+    if input.starts_with("magnet:") {
+        let magnet = Magnet::parse(input).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if let Some(v1) = &magnet.info_hash_v1 {
+            println!("info hash v1: {}", to_hex(v1));
+        }
+        if let Some(v2) = &magnet.info_hash_v2 {
+            println!("info hash v2: {}", to_hex(v2));
+        }
+        if let Some(name) = &magnet.display_name {
+            println!("name: {name}");
+        }
+        if let Some(length) = magnet.exact_length {
+            println!("length: {length}");
+        }
+        for tracker in &magnet.trackers {
+            println!("tracker: {tracker}");
+        }
+        for url in &magnet.web_seeds {
+            println!("web seed: {url}");
+        }
+        for range in &magnet.select_only {
+            println!("select only: {}-{}", range.start(), range.end());
+        }
+    } else {
+        let metainfo = Metainfo::from_node(&read_file(input))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        println!("{}", Magnet::from_metainfo(&metainfo));
+    }
+
+    Ok(())
+}
+
+/// 由文件或目录创建 torrent 文件
+fn create(path: &str, output: &str, options: &[String]) -> io::Result<()> {
+    let invalid_input = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+
+    let mut builder = TorrentBuilder::new(path);
+    let mut trackers = Vec::new();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        if option == "--private" {
+            builder = builder.with_private(true);
+            continue;
+        }
+
+        let value = options
+            .next()
+            .ok_or_else(|| invalid_input(format!("Missing value for option '{option}'")))?;
+        builder = match option.as_str() {
+            "-a" => {
+                trackers.push(value.clone());
+                builder
+            }
+            "-c" => builder.with_comment(value),
+            "-s" => builder.with_source(value),
+            "-p" => {
+                let piece_length = value
+                    .parse()
+                    .map_err(|_| invalid_input(format!("Invalid piece length '{value}'")))?;
+                builder.with_piece_length(piece_length).map_err(|e| invalid_input(e.to_string()))?
+            }
+            _ => return Err(invalid_input(format!("Unknown option '{option}'"))),
+        };
+    }
+
+    // 第一个 tracker 作为 announce, 多个 tracker 时每个 tracker 为一个 tier
+    if let Some(announce) = trackers.first() {
+        builder = builder.with_announce(announce);
+    }
+    if trackers.len() > 1 {
+        builder = builder.with_announce_list(trackers.into_iter().map(|t| vec![t]).collect());
+    }
+
+    let buffer = builder
+        .build()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(output, &buffer)?;
+
+    let metainfo = Metainfo::from_bytes(buffer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    println!("info hash v1: {}", to_hex(&metainfo.info_hashes.v1));
+
+    Ok(())
+}
+
+/// 校验目录中的数据，不匹配时以非 0 的状态码退出
+fn verify_data(torrent_path: &str, dir: &str) -> io::Result<()> {
+    let metainfo = Metainfo::from_node(&read_file(torrent_path))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let report = verify(&metainfo, dir.as_ref()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    for file in &report.files {
+        println!("{:?}\t{}", file.status, file.path.display());
+    }
+    if !report.pieces.is_empty() {
+        let passed = report.pieces.iter().filter(|s| **s == VerifyStatus::Pass).count();
+        println!("pieces: {passed}/{} passed", report.pieces.len());
+    }
+
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
 
     Ok(())
 }
@@ -0,0 +1,71 @@
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+// BEP 23 / BEP 5 中的 compact 地址格式：IPv4 为 4 字节地址 + 2 字节端口，
+// IPv6 为 16 字节地址 + 2 字节端口，均为网络字节序
+
+pub(crate) const COMPACT_V4_LEN: usize = 6;
+pub(crate) const COMPACT_V6_LEN: usize = 18;
+
+/// 解析单个 compact 地址，长度为 6 时是 IPv4, 长度为 18 时是 IPv6
+pub(crate) fn parse_compact_addr(bytes: &[u8]) -> Option<SocketAddr> {
+    let (ip, port) = bytes.split_at_checked(bytes.len().checked_sub(2)?)?;
+    let port = u16::from_be_bytes([port[0], port[1]]);
+
+    match ip.len() {
+        4 => {
+            let ip: [u8; 4] = ip.try_into().ok()?;
+            Some(SocketAddrV4::new(Ipv4Addr::from(ip), port).into())
+        }
+        16 => {
+            let ip: [u8; 16] = ip.try_into().ok()?;
+            Some(SocketAddrV6::new(Ipv6Addr::from(ip), port, 0, 0).into())
+        }
+        _ => None,
+    }
+}
+
+/// 解析连续的 compact 地址，addr_len 为每个地址的长度，总长度不是 addr_len 的倍数时返回 None
+pub(crate) fn parse_compact_addrs(bytes: &[u8], addr_len: usize) -> Option<Vec<SocketAddr>> {
+    if !bytes.len().is_multiple_of(addr_len) {
+        return None;
+    }
+
+    bytes.chunks_exact(addr_len).map(parse_compact_addr).collect()
+}
+
+/// 将地址编码为 compact 格式
+pub(crate) fn encode_compact_addr(addr: &SocketAddr, buffer: &mut Vec<u8>) {
+    match addr {
+        SocketAddr::V4(addr) => buffer.extend_from_slice(&addr.ip().octets()),
+        SocketAddr::V6(addr) => buffer.extend_from_slice(&addr.ip().octets()),
+    }
+    buffer.extend_from_slice(&addr.port().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_addr() {
+        let v4: SocketAddr = "1.2.3.4:6881".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:80".parse().unwrap();
+
+        let mut buffer = Vec::new();
+        encode_compact_addr(&v4, &mut buffer);
+        assert_eq!(vec![1, 2, 3, 4, 0x1a, 0xe1], buffer);
+        assert_eq!(Some(v4), parse_compact_addr(&buffer));
+
+        encode_compact_addr(&v4, &mut buffer);
+        assert_eq!(Some(vec![v4, v4]), parse_compact_addrs(&buffer, COMPACT_V4_LEN));
+        assert_eq!(None, parse_compact_addrs(&buffer[1..], COMPACT_V4_LEN));
+
+        let mut buffer = Vec::new();
+        encode_compact_addr(&v6, &mut buffer);
+        assert_eq!(COMPACT_V6_LEN, buffer.len());
+        assert_eq!(Some(vec![v6]), parse_compact_addrs(&buffer, COMPACT_V6_LEN));
+
+        assert_eq!(None, parse_compact_addr(&[1, 2, 3]));
+        assert_eq!(None, parse_compact_addr(&[]));
+    }
+}
@@ -0,0 +1,540 @@
+use std::fmt::Display;
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, Unexpected,
+    VariantAccess, Visitor,
+};
+
+use crate::{BdecodeError, BdecodeNode, BdecodeResult, Dict, List};
+
+impl de::Error for BdecodeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        BdecodeError::Custom(msg.to_string())
+    }
+}
+
+impl BdecodeError {
+    /// 为没有位置信息的错误补充出错节点在 buffer 中的位置
+    fn at(self, offset: usize) -> Self {
+        match self {
+            BdecodeError::Custom(msg) => BdecodeError::Deserialize { msg, offset },
+            e => e,
+        }
+    }
+}
+
+/// 从解析后的节点中反序列化出 T
+///
+/// 字符串和字节串可以直接借用 node 的 buffer, 例如 `&'de str` 和 `&'de [u8]` 。
+pub fn from_node<'de, T: de::Deserialize<'de>, B: AsRef<[u8]>>(node: &'de BdecodeNode<B>) -> BdecodeResult<T> {
+    T::deserialize(Deserializer::new(node))
+}
+
+/// 解析 buffer 并反序列化出 T
+pub fn from_bytes<T: DeserializeOwned>(buffer: Vec<u8>) -> BdecodeResult<T> {
+    let node = BdecodeNode::parse_buffer(buffer)?;
+
+    from_node(&node)
+}
+
+/// 基于 BdecodeNode 的 serde 反序列化器
+///
+/// bencoding 中没有 bool, 浮点数和 null, 所以：
+/// - bool 从整数 0 或 1 中读取；
+/// - Option 字段在 dict 中缺失时为 None, 存在时为 Some;
+/// - 枚举的 unit variant 以字符串表示，其他 variant 以只有一个 key 的 dict 表示。
+pub struct Deserializer<'de, B = Vec<u8>> {
+    node: BdecodeNode<B>,
+
+    /// root 节点的 buffer, 所有子节点都共享这个 buffer
+    buffer: &'de [u8],
+}
+
+impl<'de, B: AsRef<[u8]>> Deserializer<'de, B> {
+    pub fn new(node: &'de BdecodeNode<B>) -> Self {
+        Self {
+            node: node.clone(),
+            buffer: node.buffer_bytes(),
+        }
+    }
+
+    fn child(&self, node: BdecodeNode<B>) -> Self {
+        Self {
+            node,
+            buffer: self.buffer,
+        }
+    }
+
+    fn offset(&self) -> usize {
+        self.node.offset()
+    }
+
+    /// 借用当前字符串节点在 buffer 中的字节
+    fn borrowed_bytes(&self) -> Option<&'de [u8]> {
+        let BdecodeNode::Str(inner_node) = &self.node else {
+            return None;
+        };
+
+        Some(&self.buffer[inner_node.value_range()])
+    }
+
+    fn unexpected(&self) -> Unexpected<'de> {
+        match &self.node {
+            BdecodeNode::Int(inner_node) => match inner_node.value() {
+                Ok(v) => Unexpected::Signed(v),
+                Err(_) => Unexpected::Other("integer"),
+            },
+            BdecodeNode::Str(_) => Unexpected::Bytes(self.borrowed_bytes().unwrap_or_default()),
+            BdecodeNode::List(_) => Unexpected::Seq,
+            BdecodeNode::Dict(_) => Unexpected::Map,
+            BdecodeNode::End(_) => Unexpected::Other("end"),
+        }
+    }
+
+    fn invalid_type<V: Visitor<'de>>(&self, visitor: &V) -> BdecodeError {
+        <BdecodeError as de::Error>::invalid_type(self.unexpected(), visitor).at(self.offset())
+    }
+}
+
+impl<'de, B: AsRef<[u8]>> de::Deserializer<'de> for Deserializer<'de, B> {
+    type Error = BdecodeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        let offset = self.offset();
+
+        match &self.node {
+            BdecodeNode::Int(inner_node) => visitor.visit_i64(inner_node.value()?),
+            BdecodeNode::Str(_) => {
+                let bytes = self.borrowed_bytes().unwrap_or_default();
+                visitor.visit_borrowed_bytes(bytes)
+            }
+            BdecodeNode::List(inner_node) => {
+                visitor.visit_seq(SeqDeserializer::new(&self, inner_node.clone()))
+            }
+            BdecodeNode::Dict(inner_node) => {
+                visitor.visit_map(MapDeserializer::new(&self, inner_node.clone()))
+            }
+            BdecodeNode::End(_) => Err(BdecodeError::ExpectedValue(offset)),
+        }
+        .map_err(|e: BdecodeError| e.at(offset))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        let offset = self.offset();
+
+        match &self.node {
+            BdecodeNode::Int(inner_node) => match inner_node.value()? {
+                0 => visitor.visit_bool(false),
+                1 => visitor.visit_bool(true),
+                v => Err(de::Error::invalid_value(Unexpected::Signed(v), &visitor)),
+            },
+            _ => Err(self.invalid_type(&visitor)),
+        }
+        .map_err(|e: BdecodeError| e.at(offset))
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        let Some(bytes) = self.borrowed_bytes() else {
+            return Err(self.invalid_type(&visitor));
+        };
+
+        match std::str::from_utf8(bytes) {
+            Ok(v) => visitor.visit_borrowed_str(v),
+            Err(_) => Err(de::Error::invalid_value(Unexpected::Bytes(bytes), &visitor)),
+        }
+        .map_err(|e: BdecodeError| e.at(self.offset()))
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        let Some(bytes) = self.borrowed_bytes() else {
+            return Err(self.invalid_type(&visitor));
+        };
+
+        visitor
+            .visit_borrowed_bytes(bytes)
+            .map_err(|e: BdecodeError| e.at(self.offset()))
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        // bencoding 中没有 null, 节点存在即为 Some
+        let offset = self.offset();
+        visitor.visit_some(self).map_err(|e: BdecodeError| e.at(offset))
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        visitor.visit_unit().map_err(|e: BdecodeError| e.at(self.offset()))
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> BdecodeResult<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> BdecodeResult<V::Value> {
+        let offset = self.offset();
+        visitor.visit_newtype_struct(self).map_err(|e: BdecodeError| e.at(offset))
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        let BdecodeNode::List(inner_node) = &self.node else {
+            return Err(self.invalid_type(&visitor));
+        };
+
+        visitor
+            .visit_seq(SeqDeserializer::new(&self, inner_node.clone()))
+            .map_err(|e: BdecodeError| e.at(self.offset()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> BdecodeResult<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> BdecodeResult<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        let BdecodeNode::Dict(inner_node) = &self.node else {
+            return Err(self.invalid_type(&visitor));
+        };
+
+        visitor
+            .visit_map(MapDeserializer::new(&self, inner_node.clone()))
+            .map_err(|e: BdecodeError| e.at(self.offset()))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> BdecodeResult<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> BdecodeResult<V::Value> {
+        let offset = self.offset();
+
+        match &self.node {
+            // unit variant
+            BdecodeNode::Str(_) => visitor.visit_enum(EnumDeserializer {
+                variant: self.child(self.node.clone()),
+                value: None,
+            }),
+            // 其他 variant 都是只有一个 key 的 dict
+            BdecodeNode::Dict(inner_node) if inner_node.len() == 1 => {
+                let (key, val) = inner_node.item(0);
+                visitor.visit_enum(EnumDeserializer {
+                    variant: self.child(key),
+                    value: Some(self.child(val)),
+                })
+            }
+            _ => Err(self.invalid_type(&visitor)),
+        }
+        .map_err(|e: BdecodeError| e.at(offset))
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        let Some(bytes) = self.borrowed_bytes() else {
+            return Err(self.invalid_type(&visitor));
+        };
+
+        match std::str::from_utf8(bytes) {
+            Ok(v) => visitor.visit_borrowed_str(v),
+            Err(_) => visitor.visit_borrowed_bytes(bytes),
+        }
+        .map_err(|e: BdecodeError| e.at(self.offset()))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> BdecodeResult<V::Value> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64
+    }
+}
+
+/// 用于反序列化 list
+struct SeqDeserializer<'de, B> {
+    list: List<B>,
+    buffer: &'de [u8],
+    index: usize,
+}
+
+impl<'de, B: AsRef<[u8]>> SeqDeserializer<'de, B> {
+    fn new(de: &Deserializer<'de, B>, list: List<B>) -> Self {
+        Self {
+            list,
+            buffer: de.buffer,
+            index: 0,
+        }
+    }
+}
+
+impl<'de, B: AsRef<[u8]>> SeqAccess<'de> for SeqDeserializer<'de, B> {
+    type Error = BdecodeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> BdecodeResult<Option<T::Value>> {
+        if self.index >= self.list.len() {
+            return Ok(None);
+        }
+
+        let node = self.list.item(self.index);
+        self.index += 1;
+
+        seed.deserialize(Deserializer { node, buffer: self.buffer }).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.list.len() - self.index)
+    }
+}
+
+/// 用于反序列化 dict
+struct MapDeserializer<'de, B> {
+    dict: Dict<B>,
+    buffer: &'de [u8],
+    index: usize,
+    value: Option<BdecodeNode<B>>,
+}
+
+impl<'de, B: AsRef<[u8]>> MapDeserializer<'de, B> {
+    fn new(de: &Deserializer<'de, B>, dict: Dict<B>) -> Self {
+        Self {
+            dict,
+            buffer: de.buffer,
+            index: 0,
+            value: None,
+        }
+    }
+}
+
+impl<'de, B: AsRef<[u8]>> MapAccess<'de> for MapDeserializer<'de, B> {
+    type Error = BdecodeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> BdecodeResult<Option<K::Value>> {
+        if self.index >= self.dict.len() {
+            return Ok(None);
+        }
+
+        let (key, val) = self.dict.item(self.index);
+        self.index += 1;
+        self.value = Some(val);
+
+        seed.deserialize(Deserializer { node: key, buffer: self.buffer }).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> BdecodeResult<V::Value> {
+        let node = self.value.take().expect("next_value_seed called before next_key_seed");
+
+        seed.deserialize(Deserializer { node, buffer: self.buffer })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.dict.len() - self.index)
+    }
+}
+
+/// 用于反序列化枚举
+struct EnumDeserializer<'de, B> {
+    variant: Deserializer<'de, B>,
+    value: Option<Deserializer<'de, B>>,
+}
+
+impl<'de, B: AsRef<[u8]>> EnumAccess<'de> for EnumDeserializer<'de, B> {
+    type Error = BdecodeError;
+    type Variant = VariantDeserializer<'de, B>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> BdecodeResult<(V::Value, Self::Variant)> {
+        let variant = seed.deserialize(self.variant)?;
+
+        Ok((variant, VariantDeserializer { value: self.value }))
+    }
+}
+
+struct VariantDeserializer<'de, B> {
+    value: Option<Deserializer<'de, B>>,
+}
+
+impl<'de, B: AsRef<[u8]>> VariantAccess<'de> for VariantDeserializer<'de, B> {
+    type Error = BdecodeError;
+
+    fn unit_variant(self) -> BdecodeResult<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> BdecodeResult<T::Value> {
+        match self.value {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &"newtype variant")),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> BdecodeResult<V::Value> {
+        match self.value {
+            Some(value) => de::Deserializer::deserialize_seq(value, visitor),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &"tuple variant")),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> BdecodeResult<V::Value> {
+        match self.value {
+            Some(value) => de::Deserializer::deserialize_map(value, visitor),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &"struct variant")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct File {
+        length: i64,
+        path: Vec<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Info {
+        name: String,
+        #[serde(rename = "piece length")]
+        piece_length: u32,
+        #[serde(with = "serde_bytes")]
+        pieces: Vec<u8>,
+        files: Option<Vec<File>>,
+        private: Option<bool>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Torrent {
+        announce: String,
+        comment: Option<String>,
+        info: Info,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Message {
+        Ping,
+        Announce(u16),
+        Peer { ip: String, port: u16 },
+    }
+
+    #[test]
+    fn test_deserialize_struct() {
+        let buffer = "d 8:announce 3:url 4:info d 5:files l d 6:length i3e 4:path l 1:a 1:b e e e \
+            4:name 1:n 12:piece_length i16384e 6:pieces 4:\x01\x02\x03\x04 7:private i1e e e"
+            .replace(" ", "")
+            .replace("piece_length", "piece length");
+        let torrent: Torrent = from_bytes(buffer.into()).unwrap();
+
+        assert_eq!(
+            Torrent {
+                announce: "url".into(),
+                comment: None,
+                info: Info {
+                    name: "n".into(),
+                    piece_length: 16384,
+                    pieces: vec![1, 2, 3, 4],
+                    files: Some(vec![File { length: 3, path: vec!["a".into(), "b".into()] }]),
+                    private: Some(true),
+                },
+            },
+            torrent
+        );
+    }
+
+    #[test]
+    fn test_deserialize_borrowed() {
+        // {"k1": "v1", "k2": "v2"}
+        let buffer = "d 2:k1 2:v1 2:k2 2:v2 e".replace(" ", "");
+        let node = BdecodeNode::parse_buffer(buffer.into()).unwrap();
+
+        let map: HashMap<&str, &[u8]> = from_node(&node).unwrap();
+        assert_eq!(b"v1", map["k1"]);
+        assert_eq!(b"v2", map["k2"]);
+
+        let list: Vec<(i64, String)> = from_bytes("l l i1e 1:a e l i-2e 1:b e e".replace(" ", "").into()).unwrap();
+        assert_eq!(vec![(1, "a".to_string()), (-2, "b".to_string())], list);
+    }
+
+    #[test]
+    fn test_deserialize_enum() {
+        let msg: Message = from_bytes("4:Ping".into()).unwrap();
+        assert_eq!(Message::Ping, msg);
+
+        let msg: Message = from_bytes("d 8:Announce i6881e e".replace(" ", "").into()).unwrap();
+        assert_eq!(Message::Announce(6881), msg);
+
+        let msg: Message = from_bytes("d 4:Peer d 2:ip 2:::4:port i1e e e".replace(" ", "").into()).unwrap();
+        assert_eq!(Message::Peer { ip: "::".into(), port: 1 }, msg);
+    }
+
+    #[test]
+    fn test_deserialize_error_offset() {
+        // piece length 的类型错误，位置是 "1:x" 的起始位置
+        let buffer = "d 8:announce 3:url 4:info d 4:name 1:n 12:piece_length 1:x 6:pieces 0: e e"
+            .replace(" ", "")
+            .replace("piece_length", "piece length");
+        let err = from_bytes::<Torrent>(buffer.into()).unwrap_err();
+        assert!(matches!(err, BdecodeError::Deserialize { offset: 47, .. }), "{err}");
+
+        // 缺少 info 字段，位置是 root dict 的起始位置
+        let buffer = "d 8:announce 3:url e".replace(" ", "");
+        let err = from_bytes::<Torrent>(buffer.into()).unwrap_err();
+        assert!(matches!(err, BdecodeError::Deserialize { offset: 0, .. }), "{err}");
+
+        let err = from_bytes::<u8>("i256e".into()).unwrap_err();
+        assert!(matches!(err, BdecodeError::Deserialize { offset: 0, .. }), "{err}");
+
+        let err = from_bytes::<bool>("i2e".into()).unwrap_err();
+        assert!(matches!(err, BdecodeError::Deserialize { offset: 0, .. }), "{err}");
+    }
+}
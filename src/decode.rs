@@ -9,15 +9,19 @@ mod utils;
 mod macros;
 mod commons;
 mod token;
+mod incremental;
+mod options;
+mod path;
+mod report;
 
-use std::{borrow::Cow, collections::HashMap, sync::Arc};
+use std::{borrow::Cow, cmp::Ordering, collections::HashMap, ops::Range, sync::Arc};
 
-use commons::limits::{self, BUFFER_MAX_OFFSET, DEFAULT_DEPTH_LIMIT, DEFAULT_TOKEN_LIMIT};
+use commons::limits;
 use stack_frame::{StackFrame, StackFrameBuilder};
-use token::{BdecodeToken, BdecodeTokenType};
-use utils::{check_integer, gen_item_indexes, parse_uint};
+use token::{BdecodeTokenType, BdecodeTokens, WideBdecodeToken};
+use utils::{check_canonical_integer, check_integer, parse_uint};
 
-pub use {dict::*, end::*, int::*, list::*, node::*, str::*};
+pub use {dict::*, end::*, incremental::*, int::*, list::*, node::*, options::*, path::{BdecodePath, PathSegment}, report::render_excerpt, str::*};
 
 use crate::{BdecodeError, BdecodeResult};
 
@@ -27,24 +31,40 @@ pub enum Style {
     Pretty(usize),
 }
 
+/// dict 中 key 到 value 节点的映射，key 借用节点的 buffer
+pub type DictItems<'a, B = Vec<u8>> = HashMap<Cow<'a, [u8]>, BdecodeNode<B>>;
+
 /// 用于存放解析后的数据
-#[derive(Clone)]
-pub enum BdecodeNode {
-    Dict(Dict),
-    List(List),
-    Str(Str),
-    Int(Int),
-    End(End),
+///
+/// B 为存放解析前字符串的 buffer 的类型，可以是 `Vec<u8>`, `bytes::Bytes` 或 `&[u8]` 等任何实现了 `AsRef<[u8]>` 的类型，
+/// 解析时不会复制 buffer, 参见 parse_from()
+pub enum BdecodeNode<B = Vec<u8>> {
+    Dict(Dict<B>),
+    List(List<B>),
+    Str(Str<B>),
+    Int(Int<B>),
+    End(End<B>),
 }
 
-impl BdecodeNode {
+impl<B> Clone for BdecodeNode<B> {
+    fn clone(&self) -> Self {
+        match self {
+            BdecodeNode::Dict(inner_node) => BdecodeNode::Dict(inner_node.clone()),
+            BdecodeNode::List(inner_node) => BdecodeNode::List(inner_node.clone()),
+            BdecodeNode::Str(inner_node) => BdecodeNode::Str(inner_node.clone()),
+            BdecodeNode::Int(inner_node) => BdecodeNode::Int(inner_node.clone()),
+            BdecodeNode::End(inner_node) => BdecodeNode::End(inner_node.clone()),
+        }
+    }
+}
+
+impl<B: AsRef<[u8]>> BdecodeNode<B> {
     pub fn new(
         token_idx: u32,
-        tokens: Arc<Vec<BdecodeToken>>,
-        buffer: Arc<Vec<u8>>,
-    ) -> BdecodeNode {
-        let token = &tokens[token_idx as usize];
-        let node = match token.node_type() {
+        tokens: Arc<BdecodeTokens>,
+        buffer: Arc<B>,
+    ) -> BdecodeNode<B> {
+        match tokens.node_type(token_idx as usize) {
             BdecodeTokenType::Str => {
                 let v = Str::new(buffer, tokens, token_idx);
                 BdecodeNode::Str(v)
@@ -54,14 +74,12 @@ impl BdecodeNode {
                 BdecodeNode::Int(v)
             }
             BdecodeTokenType::List => {
-                let (item_indexes, len) = gen_item_indexes(&tokens, token_idx as usize);
-                let v = List::new(buffer, tokens, token_idx, item_indexes, len);
+                let v = List::new(buffer, tokens, token_idx);
 
                 BdecodeNode::List(v)
             }
             BdecodeTokenType::Dict => {
-                let (item_indexes, len) = gen_item_indexes(&tokens, token_idx as usize);
-                let v = Dict::new(buffer, tokens, token_idx, item_indexes, len);
+                let v = Dict::new(buffer, tokens, token_idx);
 
                 BdecodeNode::Dict(v)
             }
@@ -69,8 +87,63 @@ impl BdecodeNode {
                 let v = End::new(buffer, tokens, token_idx);
                 BdecodeNode::End(v)
             }
+        }
+    }
+
+    /// 当前节点在 buffer 中的起始位置
+    pub fn offset(&self) -> usize {
+        let (tokens, token_idx) = match self {
+            BdecodeNode::Dict(inner_node) => (&inner_node.tokens, inner_node.token_index),
+            BdecodeNode::List(inner_node) => (&inner_node.tokens, inner_node.token_index),
+            BdecodeNode::Str(inner_node) => (&inner_node.tokens, inner_node.token_index),
+            BdecodeNode::Int(inner_node) => (&inner_node.tokens, inner_node.token_index),
+            BdecodeNode::End(inner_node) => (&inner_node.tokens, inner_node.token_index),
         };
-        node
+
+        tokens.offset(token_idx as usize)
+    }
+
+    /// 当前节点在 buffer 中的范围，包括字符串的 header, 整数的 'i' 和 'e', 以及 dict 和 list 的结尾 'e'
+    pub fn byte_range(&self) -> Range<usize> {
+        match self {
+            BdecodeNode::Dict(inner_node) => inner_node.byte_range(),
+            BdecodeNode::List(inner_node) => inner_node.byte_range(),
+            BdecodeNode::Str(inner_node) => inner_node.byte_range(),
+            BdecodeNode::Int(inner_node) => inner_node.byte_range(),
+            BdecodeNode::End(inner_node) => inner_node.byte_range(),
+        }
+    }
+
+    /// 当前节点在 buffer 中对应的原始字节，例如用于计算 info dict 的 hash
+    pub fn raw_bytes(&self) -> &[u8] {
+        &self.buffer_bytes()[self.byte_range()]
+    }
+
+    /// 存放解析前字符串的 buffer 中的全部字节
+    pub fn buffer_bytes(&self) -> &[u8] {
+        (**self.buffer()).as_ref()
+    }
+
+    /// 当前节点从 root 开始的路径，例如 `info.files[3].path[0]`
+    pub fn path(&self) -> BdecodePath {
+        match self {
+            BdecodeNode::Dict(inner_node) => inner_node.path(),
+            BdecodeNode::List(inner_node) => inner_node.path(),
+            BdecodeNode::Str(inner_node) => inner_node.path(),
+            BdecodeNode::Int(inner_node) => inner_node.path(),
+            BdecodeNode::End(inner_node) => inner_node.path(),
+        }
+    }
+
+    /// 存放解析前字符串的 buffer
+    pub fn buffer(&self) -> &Arc<B> {
+        match self {
+            BdecodeNode::Dict(inner_node) => &inner_node.buffer,
+            BdecodeNode::List(inner_node) => &inner_node.buffer,
+            BdecodeNode::Str(inner_node) => &inner_node.buffer,
+            BdecodeNode::Int(inner_node) => &inner_node.buffer,
+            BdecodeNode::End(inner_node) => &inner_node.buffer,
+        }
     }
 
     pub fn as_int(&self) -> BdecodeResult<i64> {
@@ -81,7 +154,7 @@ impl BdecodeNode {
         inner_node.value()
     }
 
-    pub fn as_str(&self) -> Cow<[u8]> {
+    pub fn as_str(&self) -> Cow<'_, [u8]> {
         let BdecodeNode::Str(inner_node) = self else {
             panic!("not a Str node")
         };
@@ -99,7 +172,11 @@ impl BdecodeNode {
         }
     }
 
-    pub fn list_item(&self, index: usize) -> BdecodeNode {
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn list_item(&self, index: usize) -> BdecodeNode<B> {
         let BdecodeNode::List(inner_node) = self else {
             panic!("not a List node")
         };
@@ -115,7 +192,7 @@ impl BdecodeNode {
         inner_node.as_int(index)
     }
 
-    pub fn list_item_as_str(&self, index: usize) -> Cow<[u8]> {
+    pub fn list_item_as_str(&self, index: usize) -> Cow<'_, [u8]> {
         let BdecodeNode::List(inner_node) = self else {
             panic!("not a List node")
         };
@@ -123,7 +200,7 @@ impl BdecodeNode {
         inner_node.as_str(index)
     }
 
-    pub fn dict_item(&self, index: usize) -> (BdecodeNode, BdecodeNode) {
+    pub fn dict_item(&self, index: usize) -> (BdecodeNode<B>, BdecodeNode<B>) {
         let BdecodeNode::Dict(inner_node) = self else {
             panic!("not a Dict node")
         };
@@ -131,7 +208,7 @@ impl BdecodeNode {
         inner_node.item(index)
     }
 
-    pub fn dict_find(&self, key: &[u8]) -> Option<BdecodeNode> {
+    pub fn dict_find(&self, key: &[u8]) -> Option<BdecodeNode<B>> {
         let BdecodeNode::Dict(inner_node) = self else {
             panic!("not a Dict node")
         };
@@ -139,7 +216,7 @@ impl BdecodeNode {
         inner_node.find(key)
     }
 
-    pub fn dict_find_as_str(&self, key: &[u8]) -> Option<Cow<[u8]>> {
+    pub fn dict_find_as_str(&self, key: &[u8]) -> Option<Cow<'_, [u8]>> {
         let BdecodeNode::Dict(inner_node) = self else {
             panic!("not a Dict node")
         };
@@ -155,7 +232,7 @@ impl BdecodeNode {
         inner_node.find_as_int(key)
     }
 
-    pub fn dict_find_as_list(&self, key: &[u8]) -> Option<Vec<BdecodeNode>> {
+    pub fn dict_find_as_list(&self, key: &[u8]) -> Option<Vec<BdecodeNode<B>>> {
         let BdecodeNode::Dict(inner_node) = self else {
             panic!("not a Dict node")
         };
@@ -163,7 +240,7 @@ impl BdecodeNode {
         inner_node.find_as_list(key)
     }
 
-    pub fn dict_find_as_dict(&self, key: &[u8]) -> Option<HashMap<Cow<[u8]>, BdecodeNode>> {
+    pub fn dict_find_as_dict(&self, key: &[u8]) -> Option<HashMap<Cow<'_, [u8]>, BdecodeNode<B>>> {
         let BdecodeNode::Dict(inner_node) = self else {
             panic!("not a Dict node")
         };
@@ -171,54 +248,411 @@ impl BdecodeNode {
         inner_node.find_as_dict(key)
     }
 
+    pub fn is_dict(&self) -> bool {
+        matches!(self, BdecodeNode::Dict(_))
+    }
+
+    pub fn is_list(&self) -> bool {
+        matches!(self, BdecodeNode::List(_))
+    }
+
+    pub fn is_str(&self) -> bool {
+        matches!(self, BdecodeNode::Str(_))
+    }
+
+    pub fn is_int(&self) -> bool {
+        matches!(self, BdecodeNode::Int(_))
+    }
+
+    /// 节点类型的名称，用于错误信息
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            BdecodeNode::Dict(_) => "dict",
+            BdecodeNode::List(_) => "list",
+            BdecodeNode::Str(_) => "str",
+            BdecodeNode::Int(_) => "int",
+            BdecodeNode::End(_) => "end",
+        }
+    }
+
+    fn type_mismatch(&self, expected: &'static str) -> BdecodeError {
+        BdecodeError::TypeMismatch {
+            expected,
+            found: self.type_name(),
+            offset: self.offset(),
+        }
+        .with_path(self.path())
+    }
+
+    /// 类型不匹配时返回 TypeMismatch 错误
+    pub fn try_as_dict(&self) -> BdecodeResult<&Dict<B>> {
+        match self {
+            BdecodeNode::Dict(inner_node) => Ok(inner_node),
+            _ => Err(self.type_mismatch("dict")),
+        }
+    }
+
+    /// 类型不匹配时返回 TypeMismatch 错误
+    pub fn try_as_list(&self) -> BdecodeResult<&List<B>> {
+        match self {
+            BdecodeNode::List(inner_node) => Ok(inner_node),
+            _ => Err(self.type_mismatch("list")),
+        }
+    }
+
+    /// 与 as_int() 相同，但类型不匹配时返回 TypeMismatch 错误，而不是 panic
+    pub fn try_as_int(&self) -> BdecodeResult<i64> {
+        match self {
+            BdecodeNode::Int(inner_node) => inner_node.value(),
+            _ => Err(self.type_mismatch("int")),
+        }
+    }
+
+    /// 与 as_str() 相同，但类型不匹配时返回 TypeMismatch 错误，而不是 panic
+    pub fn try_as_str(&self) -> BdecodeResult<Cow<'_, [u8]>> {
+        match self {
+            BdecodeNode::Str(inner_node) => Ok(inner_node.value()),
+            _ => Err(self.type_mismatch("str")),
+        }
+    }
+
+    /// 与 len() 相同，但不是 list 或 dict 时返回 TypeMismatch 错误，而不是 panic
+    pub fn try_len(&self) -> BdecodeResult<usize> {
+        match self {
+            BdecodeNode::List(inner_node) => Ok(inner_node.len()),
+            BdecodeNode::Dict(inner_node) => Ok(inner_node.len()),
+            _ => Err(self.type_mismatch("list or dict")),
+        }
+    }
+
+    /// 与 list_item() 相同，但类型不匹配时返回 TypeMismatch 错误，索引越界时返回 IndexOutOfRange 错误
+    pub fn try_list_item(&self, index: usize) -> BdecodeResult<BdecodeNode<B>> {
+        self.try_as_list()?.try_item(index)
+    }
+
+    /// 参见 try_list_item() 和 try_as_int()
+    pub fn try_list_item_as_int(&self, index: usize) -> BdecodeResult<i64> {
+        self.try_list_item(index)?.try_as_int()
+    }
+
+    /// 参见 try_list_item() 和 try_as_str()
+    pub fn try_list_item_as_str(&self, index: usize) -> BdecodeResult<Cow<'_, [u8]>> {
+        let item = self.try_list_item(index)?;
+
+        // item 与当前节点共享同一个 buffer
+        Ok(Cow::Borrowed(&self.buffer_bytes()[str_value_range(&item)?]))
+    }
+
+    /// 与 dict_item() 相同，但类型不匹配时返回 TypeMismatch 错误，索引越界时返回 IndexOutOfRange 错误
+    pub fn try_dict_item(&self, index: usize) -> BdecodeResult<(BdecodeNode<B>, BdecodeNode<B>)> {
+        self.try_as_dict()?.try_item(index)
+    }
+
+    /// 与 dict_find() 相同，但当前节点不是 dict 时返回 TypeMismatch 错误，而不是 panic
+    pub fn try_dict_find(&self, key: &[u8]) -> BdecodeResult<Option<BdecodeNode<B>>> {
+        Ok(self.try_as_dict()?.find(key))
+    }
+
+    /// 查找 key 对应的字符串，key 不存在时返回 None, 当前节点不是 dict 或者 value 不是字符串时返回 TypeMismatch 错误
+    pub fn try_dict_find_as_str(&self, key: &[u8]) -> BdecodeResult<Option<Cow<'_, [u8]>>> {
+        let Some(value) = self.try_dict_find(key)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(Cow::Borrowed(&self.buffer_bytes()[str_value_range(&value)?])))
+    }
+
+    /// 查找 key 对应的整数，key 不存在时返回 None, 当前节点不是 dict 或者 value 不是整数时返回 TypeMismatch 错误
+    pub fn try_dict_find_as_int(&self, key: &[u8]) -> BdecodeResult<Option<i64>> {
+        self.try_dict_find(key)?.map(|value| value.try_as_int()).transpose()
+    }
+
+    /// 查找 key 对应的 list 的所有 item, key 不存在时返回 None, 当前节点不是 dict 或者 value 不是 list 时返回 TypeMismatch 错误
+    pub fn try_dict_find_as_list(&self, key: &[u8]) -> BdecodeResult<Option<Vec<BdecodeNode<B>>>> {
+        let Some(value) = self.try_dict_find(key)? else {
+            return Ok(None);
+        };
+        let list = value.try_as_list()?;
+
+        Ok(Some((0..list.len()).map(|i| list.item(i)).collect()))
+    }
+
+    /// 查找 key 对应的 dict 的所有 item, key 不存在时返回 None, 当前节点不是 dict 或者 value 不是 dict 时返回 TypeMismatch 错误
+    pub fn try_dict_find_as_dict(&self, key: &[u8]) -> BdecodeResult<Option<DictItems<'_, B>>> {
+        let Some(value) = self.try_dict_find(key)? else {
+            return Ok(None);
+        };
+        let dict = value.try_as_dict()?;
+
+        let mut node_map = HashMap::new();
+        for i in 0..dict.len() {
+            let (key, value) = dict.item(i);
+            node_map.insert(Cow::Borrowed(&self.buffer_bytes()[str_value_range(&key)?]), value);
+        }
+
+        Ok(Some(node_map))
+    }
+
+    /// 解析任意类型的 buffer, 与 parse() 相同，但节点直接持有传入的 buffer, 不会复制数据
+    ///
+    /// 例如从网络收到的 `bytes::Bytes` 可以直接解析，或者通过 `&[u8]` 借用调用方的数据。
+    pub fn parse_from(
+        buffer: B,
+        depth_limit: Option<usize>,
+        token_limit: Option<i32>,
+    ) -> BdecodeResult<Self> {
+        Self::parse_with_options(buffer, &ParseOptions::from_limits(depth_limit, token_limit))
+    }
+
+    /// 解析任意类型的 buffer, 与 parse_exact() 相同，参见 parse_from()
+    pub fn parse_exact_from(
+        buffer: B,
+        depth_limit: Option<usize>,
+        token_limit: Option<i32>,
+    ) -> BdecodeResult<Self> {
+        let options = ParseOptions::from_limits(depth_limit, token_limit).with_trailing_data(TrailingDataPolicy::Reject);
+
+        Self::parse_with_options(buffer, &options)
+    }
+
+    /// 按照解析选项解析任意类型的 buffer, 参见 ParseOptions
+    pub fn parse_with_options(buffer: B, options: &ParseOptions) -> BdecodeResult<Self> {
+        let len = buffer.as_ref().len();
+        let (node, consumed) = Self::parse_impl(buffer, options)?;
+        if options.trailing_data == TrailingDataPolicy::Reject && consumed != len {
+            return Err(BdecodeError::TrailingData(consumed));
+        }
+
+        Ok(node)
+    }
+
+    /// 解析 buffer 开头的一个完整的值，并返回该值占用的字节数，不检查之后的数据
+    fn parse_impl(buffer: B, options: &ParseOptions) -> BdecodeResult<(Self, usize)> {
+        let bytes = buffer.as_ref();
+        let mut state = ParseState::new(options);
+        state.run(bytes, false)?;
+
+        Ok(state.finish(buffer))
+    }
+
+    pub fn to_json_with_style(&self, style: Style) -> String {
+        match self {
+            BdecodeNode::Dict(inner_node) => inner_node.to_json_with_style(style),
+            BdecodeNode::List(inner_node) => inner_node.to_json_with_style(style),
+            BdecodeNode::Str(inner_node) => inner_node.to_json_with_style(style),
+            BdecodeNode::Int(inner_node) => inner_node.to_json_with_style(style),
+            BdecodeNode::End(inner_node) => inner_node.to_json_with_style(style),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        self.to_json_with_style(Style::Compact)
+    }
+
+    pub fn to_json_pretty(&self) -> String {
+        self.to_json_with_style(Style::Pretty(0))
+    }
+}
+
+impl BdecodeNode {
+    /// 解析 buffer 开头的一个完整的值，值之后的数据会被忽略，参见 parse_prefix()
     pub fn parse(
         buffer: Vec<u8>,
         depth_limit: Option<usize>,
         token_limit: Option<i32>,
     ) -> BdecodeResult<Self> {
-        let depth_limit = depth_limit.unwrap_or(DEFAULT_DEPTH_LIMIT);
-        let mut token_limit = token_limit.unwrap_or(DEFAULT_TOKEN_LIMIT as i32);
+        Self::parse_from(buffer, depth_limit, token_limit)
+    }
+
+    /// 解析 buffer 开头的一个完整的值，并返回该值占用的字节数
+    ///
+    /// 用于值之后还跟着其他数据的场景，例如 BEP 9 ut_metadata 的 data 消息在 bencode 头之后直接跟着 piece 的数据，
+    /// 或者流式协议中多个消息首尾相连。返回的节点仍然持有整个 buffer, 可以通过 `&node.buffer()[consumed..]` 读取剩余的数据。
+    pub fn parse_prefix(
+        buffer: Vec<u8>,
+        depth_limit: Option<usize>,
+        token_limit: Option<i32>,
+    ) -> BdecodeResult<(Self, usize)> {
+        Self::parse_impl(buffer, &ParseOptions::from_limits(depth_limit, token_limit))
+    }
+
+    /// 解析 buffer, 要求整个 buffer 恰好是一个完整的值，值之后还有数据时返回 TrailingData 错误
+    pub fn parse_exact(
+        buffer: Vec<u8>,
+        depth_limit: Option<usize>,
+        token_limit: Option<i32>,
+    ) -> BdecodeResult<Self> {
+        Self::parse_exact_from(buffer, depth_limit, token_limit)
+    }
+
+    /// 以严格模式解析 buffer, 只接受规范（canonical）的 bencoding 编码
+    ///
+    /// 以下情况会返回错误：
+    /// - 整数为 "-0" ，例如 "i-0e";
+    /// - 整数有前导 0, 例如 "i007e";
+    /// - 字符串长度有前导 0, 例如 "03:abc";
+    /// - dict 的 key 没有按字节序排序；
+    /// - dict 中有重复的 key 。
+    pub fn parse_strict(
+        buffer: Vec<u8>,
+        depth_limit: Option<usize>,
+        token_limit: Option<i32>,
+    ) -> BdecodeResult<Self> {
+        Self::parse_with_options(buffer, &ParseOptions::from_limits(depth_limit, token_limit).with_strict(true))
+    }
+
+    pub fn parse_buffer(buffer: Vec<u8>) -> BdecodeResult<Self> {
+        Self::parse(buffer, None, None)
+    }
+
+    /// 解析 buffer, 值之后还有数据时返回错误，参见 parse_exact()
+    pub fn parse_buffer_exact(buffer: Vec<u8>) -> BdecodeResult<Self> {
+        Self::parse_exact(buffer, None, None)
+    }
 
-        let mut tokens = Vec::<BdecodeToken>::new();
+    /// 以严格模式解析 buffer, 参见 parse_strict()
+    pub fn parse_buffer_strict(buffer: Vec<u8>) -> BdecodeResult<Self> {
+        Self::parse_strict(buffer, None, None)
+    }
+}
 
-        if buffer.len() > BUFFER_MAX_OFFSET as usize {
-            Err(BdecodeError::LimitExceeded(buffer.len()))?
+#[cfg(feature = "mmap")]
+impl BdecodeNode<memmap2::Mmap> {
+    /// 以只读的内存映射解析文件，文件内容不会被读入或复制到内存中，适合解析很大的文件
+    ///
+    /// 节点持有内存映射，所有节点被释放后映射才会被解除。
+    ///
+    /// # Safety
+    ///
+    /// 与 `memmap2::Mmap::map()` 相同：节点存活期间，文件不能被本进程或其他进程修改或截断，否则行为未定义。
+    pub unsafe fn parse_mmap(
+        file: &std::fs::File,
+        depth_limit: Option<usize>,
+        token_limit: Option<i32>,
+    ) -> BdecodeResult<Self> {
+        let mmap = unsafe { memmap2::Mmap::map(file) }.map_err(|e| BdecodeError::Io(Arc::new(e)))?;
+
+        Self::parse_from(mmap, depth_limit, token_limit)
+    }
+}
+
+/// 字符串节点的值在 buffer 中的范围，不是字符串时返回 TypeMismatch 错误
+fn str_value_range<B: AsRef<[u8]>>(node: &BdecodeNode<B>) -> BdecodeResult<Range<usize>> {
+    match node {
+        BdecodeNode::Str(inner_node) => Ok(inner_node.value_range()),
+        _ => Err(node.type_mismatch("str")),
+    }
+}
+
+/// 解析过程中的状态，增量解析时在多次调用之间保存
+pub(crate) struct ParseState {
+    options: ParseOptions,
+
+    /// 已经解析出的 token 数量，用于检查 token_limit
+    token_count: usize,
+
+    tokens: BdecodeTokens,
+
+    // stack 在解析 dict 和 list 时才会使用。
+    // 它的每一项都是存放的 dict 和 list 本身的入口 token 对应的 frame （注意不是 dict 和 list 的第一个元素的 token ）。
+    stack: Vec<StackFrame>,
+
+    // 记录 stack 中每个 dict 上一个 key 在 buffer 中的范围，用于生成出错位置的路径，严格模式下还用于检查 key 的顺序。
+    last_keys: Vec<Option<Range<usize>>>,
+
+    // 记录 stack 中每个 dict 或 list 已经解析出的 item 数量，用于检查 max_items 和生成出错位置的路径。
+    item_counts: Vec<usize>,
+
+    /// 当前正在解析的 token 是否已经计入了所在 list 的 item 数量，用于生成出错位置的路径
+    item_counted: bool,
+
+    /// 下一个要解析的 token 在 buffer 中的位置
+    start: usize,
+}
+
+impl ParseState {
+    pub(crate) fn new(options: &ParseOptions) -> Self {
+        Self {
+            options: options.clone(),
+            token_count: 0,
+            tokens: BdecodeTokens::default(),
+            stack: Vec::with_capacity(options.depth_limit.min(limits::DEFAULT_DEPTH_LIMIT)),
+            last_keys: Vec::new(),
+            item_counts: Vec::new(),
+            item_counted: false,
+            start: 0,
         }
+    }
 
-        let mut start = 0;
-        let end = buffer.len();
+    /// 从上次停下的位置继续解析 buffer, 返回顶层的值是否已经解析完成
+    ///
+    /// partial 为 true 时 buffer 中的数据可能还不完整，遇到不完整的 token 时不会报错，而是返回 false,
+    /// 等 buffer 中追加了更多的数据后，再从该 token 重新开始解析。
+    ///
+    /// 出错时，错误中附带出错位置从 root 开始的路径。
+    pub(crate) fn run(&mut self, buffer: &[u8], partial: bool) -> BdecodeResult<bool> {
+        self.run_tokens(buffer, partial)
+            .map_err(|e| e.with_path(self.error_path(buffer)))
+    }
 
-        // stack 在解析 dict 和 list 时才会使用。
-        // 它的每一项都是存放的 dict 和 list 本身的入口 token 对应的 frame （注意不是 dict 和 list 的第一个元素的 token ）。
-        let mut stack = Vec::<StackFrame>::with_capacity(depth_limit as usize);
+    /// 出错时 stack 中保存着包含出错位置的 dict 和 list, 由此生成出错位置的路径
+    fn error_path(&self, buffer: &[u8]) -> BdecodePath {
+        let mut path = BdecodePath::default();
+
+        for (i, frame) in self.stack.iter().enumerate() {
+            let is_top = i + 1 == self.stack.len();
+            let segment = if self.tokens.node_type(frame.token() as usize) == BdecodeTokenType::Dict {
+                // 开始解析 dict 或 list 类型的 value 时，外层 dict 的状态就已经切换回 key,
+                // 所以只有 stack 顶部的 dict 需要根据状态判断正在解析的是 key 还是 value
+                match (!is_top || frame.state() == 1, &self.last_keys[i]) {
+                    (true, Some(key)) => Some(PathSegment::Key(buffer[key.clone()].to_vec())),
+                    _ => None,
+                }
+            } else if !is_top || self.item_counted {
+                self.item_counts[i].checked_sub(1).map(PathSegment::Index)
+            } else {
+                None
+            };
 
-        // current_frame_ptr 只会指向已处理完的 frame , 不会指向处理一半的 frame 。
-        let mut current_frame_ptr: Option<* mut StackFrame> = None;
+            match segment {
+                Some(segment) => path.push(segment),
+                None => break,
+            }
+        }
 
-        if end == 0 {
+        path
+    }
+
+    fn run_tokens(&mut self, buffer: &[u8], partial: bool) -> BdecodeResult<bool> {
+        let end = buffer.len();
+
+        if end == 0 && !partial {
             Err(BdecodeError::UnexpectedEof(0))?
         }
 
-        while start <= end {
-            if stack.len() >= depth_limit as usize {
-                Err(BdecodeError::DepthExceeded(depth_limit as usize))?
+        while self.start <= end {
+            if partial && !token_complete(buffer, self.start, self.options.max_string_len) {
+                return Ok(false);
             }
 
-            token_limit -= 1;
-            if token_limit < 0 {
-                Err(BdecodeError::LimitExceeded(DEFAULT_TOKEN_LIMIT as usize))?
-            }
+            let mut start = self.start;
+            let stack = &mut self.stack;
+            let tokens = &mut self.tokens;
+
+            self.item_counted = false;
 
             // look for a new token
             let Some(t) = buffer.get(start) else {
                 Err(BdecodeError::UnexpectedEof(start))?
             };
 
+            // 当前所在的 dict 或 list 的 frame 在 stack 中的位置，解析完当前 token 后用于切换 dict 的 key/value 状态
+            let current_frame = stack.len().checked_sub(1);
+
             // 检查当前是否在解析 dict 或 list 的过程中
-            if let Some(stack_frame_ptr) = current_frame_ptr {
-                let stack_frame = unsafe { *stack_frame_ptr };
+            if let Some(stack_frame) = stack.last() {
                 // 检查当前是否正要解析 dict
-                if tokens[stack_frame.token() as usize].node_type() == BdecodeTokenType::Dict 
+                if tokens.node_type(stack_frame.token() as usize) == BdecodeTokenType::Dict 
                     // 检查当前是否正要解析 dict 的 key
                     && stack_frame.state() == 0 
                     // 检查当前字符是否不为数字
@@ -228,6 +662,30 @@ impl BdecodeNode {
                 {
                     Err(BdecodeError::ExpectedDigit(start))?
                 }
+
+                // 检查 list 或 dict 中 item 的数量，dict 只在解析 key 时计数
+                let is_item = tokens.node_type(stack_frame.token() as usize) == BdecodeTokenType::List
+                    || stack_frame.state() == 0;
+                if let (true, false, Some(count)) = (is_item, *t == b'e', self.item_counts.last_mut()) {
+                    *count += 1;
+                    self.item_counted = true;
+                    if *count > self.options.max_items {
+                        Err(BdecodeError::ItemCountExceeded { limit: self.options.max_items, position: start })?
+                    }
+                }
+            }
+
+            if stack.len() >= self.options.depth_limit {
+                Err(BdecodeError::DepthExceeded { limit: self.options.depth_limit, position: start })?
+            }
+
+            self.token_count += 1;
+            if self.token_count > self.options.token_limit {
+                Err(BdecodeError::TokenLimitExceeded { limit: self.options.token_limit, position: start })?
+            }
+            // 给 finish() 中的虚拟 end token 留出位置
+            if self.token_count >= limits::MAX_TOKENS {
+                Err(BdecodeError::LimitExceeded(limits::MAX_TOKENS))?
             }
 
             match t {
@@ -237,7 +695,11 @@ impl BdecodeNode {
                         .build();
                     stack.push(frame);
                     // 等 dict 解析完后再修正 next_item
-                    tokens.push(BdecodeToken::new_dict(start as u32, 0));
+                    tokens.push(WideBdecodeToken::new_dict(start as u64, 0));
+                    // 在解析 key 的过程中发现顺序不对时再清除
+                    tokens.set_keys_sorted(tokens.len() - 1, true);
+                    self.last_keys.push(None);
+                    self.item_counts.push(0);
 
                     start += 1;
                 }
@@ -247,14 +709,29 @@ impl BdecodeNode {
                         .build();
                     stack.push(frame);
                     // 等 dict 解析完后再修正 next_item
-                    tokens.push(BdecodeToken::new_list(start as u32, 0)); 
+                    tokens.push(WideBdecodeToken::new_list(start as u64, 0)); 
+                    self.last_keys.push(None);
+                    self.item_counts.push(0);
 
                     start += 1;
                 }
                 b'i' => {
                     let int_start = start;
-                    start = check_integer(buffer.as_ref(), start + 1 as usize)?;
-                    tokens.push(BdecodeToken::new_int(int_start as u32));
+                    // 整数的位数，不包括 '-'
+                    let digits = buffer[int_start + 1..]
+                        .iter()
+                        .skip(usize::from(buffer.get(int_start + 1) == Some(&b'-')))
+                        .take_while(|c| c.is_ascii_digit())
+                        .count();
+                    if digits > self.options.max_int_digits {
+                        Err(BdecodeError::IntegerDigitsExceeded { limit: self.options.max_int_digits, position: int_start })?
+                    }
+
+                    start = check_integer(buffer, start + 1)?;
+                    if self.options.strict {
+                        check_canonical_integer(&buffer[int_start + 1..start], int_start)?;
+                    }
+                    tokens.push(WideBdecodeToken::new_int(int_start as u64));
 
                     assert!(buffer[start] == b'e');
 
@@ -269,7 +746,7 @@ impl BdecodeNode {
                     // 检查当前是否在解析 dict 或 list 的过程中
                     if let Some(stack_frame) = stack.last() {
                         // 检查当前是否正要解析 dict
-                        if tokens[stack_frame.token() as usize].node_type() == BdecodeTokenType::Dict 
+                        if tokens.node_type(stack_frame.token() as usize) == BdecodeTokenType::Dict 
                             // 检查当前是否正要解析 dict 的 value
                             && stack_frame.state() == 1
                         {
@@ -278,7 +755,7 @@ impl BdecodeNode {
                     }
 
                     // 给 list 和 dict 的内部插入一个 end token，这样前一个的 item 的 next_item 就指向这个 end token.
-                    tokens.push(BdecodeToken::new_end(start as u32));
+                    tokens.push(WideBdecodeToken::new_end(start as u64));
 
                     // 计算当前 list 或 dict 的 next_item ----------
 
@@ -286,14 +763,16 @@ impl BdecodeNode {
 				    let top = stack.last().expect("stack is empty").token() as usize;
                     let next_item = tokens.len() - top;
 
-                    if next_item > limits::MAX_NEXT_ITEM {
-                        return Err(BdecodeError::LimitExceeded(limits::MAX_NEXT_ITEM));
+                    if next_item > limits::WIDE_MAX_NEXT_ITEM {
+                        return Err(BdecodeError::LimitExceeded(limits::WIDE_MAX_NEXT_ITEM));
                     }
 
-                    // next_item 就是要跳过多少个 token.
-                    tokens[top].set_next_item(next_item as u32);
+                    // next_item 就是要跳过多少个 token. 超过 BdecodeToken 的范围时，tokens 会自动转换为 WideBdecodeToken
+                    tokens.set_next_item(top, next_item as u32);
 
                     stack.pop();
+                    self.last_keys.pop();
+                    self.item_counts.pop();
                     start += 1;
                 }
                 // parse 字符串 
@@ -306,12 +785,20 @@ impl BdecodeNode {
                     let str_start = start;
                     start += 1;
 
+                    if self.options.strict && *t == b'0' && buffer.get(start).is_some_and(|c| c.is_ascii_digit()) {
+                        return Err(BdecodeError::LengthLeadingZero(str_start));
+                    }
+
                     if start >= end {
                         return Err(BdecodeError::UnexpectedEof(start));
                     }
 
                     // 解析出后续字符串的 len 值，并返回 buffer 尚未解析的 start 位置
-				    start = parse_uint(buffer.as_ref(), start, b':', &mut len)?;
+				    start = parse_uint(buffer, start, b':', &mut len)?;
+
+                    if len as usize > self.options.max_string_len {
+                        Err(BdecodeError::StringLengthExceeded { limit: self.options.max_string_len, position: str_start })?
+                    }
 
                     if start == end {
                         return Err(BdecodeError::ExpectedColon(str_start, end));
@@ -329,8 +816,8 @@ impl BdecodeNode {
                         return Err(BdecodeError::UnexpectedEof(start));
                     }
 
-                    // the bdecode_token only has 8 bits to keep the header size
-                    // in. If it overflows, fail!
+                    // BdecodeToken 只有 3 位保存 header size, 超过时 tokens 会自动转换为 WideBdecodeToken,
+                    // WideBdecodeToken 也放不下时返回错误
                     //
                     // eg: "10:abcdefghij2:kl"
                     //      ^^ ^
@@ -341,121 +828,144 @@ impl BdecodeNode {
                     //
                     // start - 1 = 2， 就是 "10" 的长度为 2
                     let header_size = start - str_start - 1;
-                    if header_size > limits::MAX_HEADER_SIZE {
-                        return Err(BdecodeError::LimitExceeded(limits::MAX_HEADER_SIZE));
+                    if header_size > limits::WIDE_MAX_HEADER_SIZE {
+                        return Err(BdecodeError::LimitExceeded(limits::WIDE_MAX_HEADER_SIZE));
                     }
 
-                    tokens.push(BdecodeToken::new_str(str_start as u32, header_size as u8));
+                    // 检查当前字符串是否为 dict 的 key, 是则返回 dict 的 token 索引
+                    let dict_token = stack.last().and_then(|frame| {
+                        let is_key = tokens.node_type(frame.token() as usize) == BdecodeTokenType::Dict
+                            && frame.state() == 0;
+                        is_key.then_some(frame.token() as usize)
+                    });
+
+                    if let (Some(dict_token), Some(last_key)) = (dict_token, self.last_keys.last_mut()) {
+                        let key = start..start + len as usize;
+                        if let Some(prev_key) = last_key {
+                            let order = buffer[prev_key.clone()].cmp(&buffer[key.clone()]);
+                            match order {
+                                Ordering::Greater if self.options.strict => return Err(BdecodeError::UnsortedKey(str_start)),
+                                Ordering::Equal if self.options.strict => return Err(BdecodeError::DuplicateKey(str_start)),
+                                Ordering::Greater | Ordering::Equal => tokens.set_keys_sorted(dict_token, false),
+                                Ordering::Less => (),
+                            }
+                        }
+                        *last_key = Some(key);
+                    }
+
+                    tokens.push(WideBdecodeToken::new_str(str_start as u64, header_size as u8));
                     // 接上面的例子, 跳过整个字符串 "abcdefghij", 指向 "2:kl" 的 '2' 位置
 				    start += len as usize;
                 }
             }
+            self.start = start;
 
-            
-            if let Some(stack_frame_prt) = current_frame_ptr {
-                let stack_frame =  unsafe { stack_frame_prt.as_mut_unchecked() };
-                // // 因为 vec 的 pop 只是将 sp - 1，并不会释放内存，所以下面的这段可以用后面的方法二代替。
-                // // 方法一：
-                // if let Some(now_frame_ptr) = stack.last() {
-                //     let now_frame_ptr = now_frame_ptr as *const StackFrame;
-                //     if now_frame_ptr >= stack_frame_prt {
-                //         if tokens[stack_frame.token() as usize].node_type() == BdecodeTokenType::Dict {
-                //             // 下一个我们解析的 Dict item 的 state 是一个相反的值，也就是从 key 切换到 value.
-                //             let _state = stack_frame.state();
-                //             stack_frame.set_state(!stack_frame.state());
-                //         }
-                //     }
-                // } 
-
-                // 方法二：
-                // 注意：如果之前 stack 调用过 pop, 则下面写入时，会写到 stack 已经 pop 掉的位置，但是不会有读取，且不会报错。
-                if tokens[stack_frame.token() as usize].node_type() == BdecodeTokenType::Dict {
-                    // 下一个我们解析的 Dict item 的 state 是一个相反的值，也就是从 key 切换到 value.
-                    let _state = stack_frame.state();
+            // 如果当前 token 之前所在的 frame 仍在 stack 中（即当前 token 不是结束它的 'e' ），并且它是 dict,
+            // 则下一个我们解析的 Dict item 的 state 是一个相反的值，也就是从 key 切换到 value.
+            if let Some(stack_frame) = current_frame.and_then(|idx| stack.get_mut(idx)) {
+                if tokens.node_type(stack_frame.token() as usize) == BdecodeTokenType::Dict {
                     stack_frame.set_state(!stack_frame.state());
                 }
             }
 
-            // 保存处理完的 frame
-            current_frame_ptr = stack.last_mut().map(|frame_ref| {
-                frame_ref as *mut StackFrame 
-            });
-
             // 如果当前栈为空，说明当前顶层节点也处理完了，则跳出循环. 也就是已经解析完整个 buffer 了。
             if stack.is_empty() {
                 break;
             }
         } // end while
 
-        // 推入一个虚拟 end token，用于结束解析
-        tokens.push(BdecodeToken::new_end(start as u32));
-
-        Ok(BdecodeNode::new(0, Arc::new(tokens), Arc::new(buffer)))
+        Ok(true)
     }
 
-    pub fn parse_buffer(buffer: Vec<u8>) -> BdecodeResult<Self> {
-        Self::parse(buffer, None, None)
+    /// 已经解析完的字节数
+    pub(crate) fn consumed(&self) -> usize {
+        self.start
     }
 
-    pub fn to_json_with_style(&self, style: Style) -> String {
-        match self {
-            BdecodeNode::Dict(inner_node) => inner_node.to_json_with_style(style),
-            BdecodeNode::List(inner_node) => inner_node.to_json_with_style(style),
-            BdecodeNode::Str(inner_node) => inner_node.to_json_with_style(style),
-            BdecodeNode::Int(inner_node) => inner_node.to_json_with_style(style),
-            BdecodeNode::End(inner_node) => inner_node.to_json_with_style(style),
-        }
-    }
+    /// 结束解析，返回顶层节点和顶层的值占用的字节数
+    pub(crate) fn finish<B: AsRef<[u8]>>(mut self, buffer: B) -> (BdecodeNode<B>, usize) {
+        // 推入一个虚拟 end token，用于结束解析
+        self.tokens.push(WideBdecodeToken::new_end(self.start as u64));
 
-    pub fn to_json(&self) -> String {
-        self.to_json_with_style(Style::Compact)
+        (BdecodeNode::new(0, Arc::new(self.tokens), Arc::new(buffer)), self.start)
     }
+}
 
-    pub fn to_json_pretty(&self) -> String {
-        self.to_json_with_style(Style::Pretty(0))
+/// 增量解析时，判断 start 处的 token 是否已经完整地在 buffer 中
+///
+/// 只有数据不完整但可能合法的 token 才返回 false; 已经可以确定不合法的 token 返回 true, 由解析过程报告错误。
+/// 字符串的长度超过 max_string_len 时也返回 true, 这样收到长度之后就能立即报错，不必等待整个字符串。
+fn token_complete(buffer: &[u8], start: usize, max_string_len: usize) -> bool {
+    // i64 和字符串长度最多有 20 位数字，超过时不再等待，由解析过程报告错误
+    const MAX_DIGITS: usize = 20;
+
+    let Some(&t) = buffer.get(start) else {
+        return false;
+    };
+
+    match t {
+        b'i' => {
+            let digits = &buffer[start + 1..];
+            let pending = digits.iter().all(|c| c.is_ascii_digit() || *c == b'-');
+
+            !pending || digits.len() > MAX_DIGITS + 1
+        }
+        b'0'..=b'9' => {
+            let Some(colon) = buffer[start..].iter().position(|c| !c.is_ascii_digit()) else {
+                return buffer.len() - start > MAX_DIGITS;
+            };
+            let colon = start + colon;
+
+            let len = std::str::from_utf8(&buffer[start..colon]).ok().and_then(|len| len.parse::<usize>().ok());
+            match (buffer[colon], len) {
+                (b':', Some(len)) => len > max_string_len || colon + 1 + len <= buffer.len(),
+                _ => true,
+            }
+        }
+        _ => true,
     }
 }
 
-impl core::fmt::Debug for BdecodeNode {
+impl<B: AsRef<[u8]>> core::fmt::Debug for BdecodeNode<B> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             BdecodeNode::Dict(inner_node) => {
                 f.debug_struct("Dict")
                     .field("token_idx", &inner_node.token_index)
-                    .field("item_indexes", &inner_node.item_indexes)
+                    .field("item_indexes", &inner_node.item_indexes())
                     .field("len", &inner_node.len())
                     .field("tokens", &inner_node.tokens)
-                    .field("buffer", &bytes::Bytes::copy_from_slice(&inner_node.buffer))
+                    .field("buffer", &bytes::Bytes::copy_from_slice(inner_node.bytes()))
                     .finish()
             }
             BdecodeNode::List(inner_node) => {
                 f.debug_struct("List")
                     .field("token_idx", &inner_node.token_index)
-                    .field("item_indexes", &inner_node.item_indexes)
+                    .field("item_indexes", &inner_node.item_indexes())
                     .field("len", &inner_node.len())
                     .field("tokens", &inner_node.tokens)
-                    .field("buffer", &bytes::Bytes::copy_from_slice(&inner_node.buffer))
+                    .field("buffer", &bytes::Bytes::copy_from_slice(inner_node.bytes()))
                     .finish()
             }
             BdecodeNode::Str(inner_node) => {
                 f.debug_struct("Str")
                     .field("token_idx", &inner_node.token_index)
                     .field("tokens", &inner_node.tokens)
-                    .field("buffer", &bytes::Bytes::copy_from_slice(&inner_node.buffer))
+                    .field("buffer", &bytes::Bytes::copy_from_slice(inner_node.bytes()))
                     .finish()
             }
             BdecodeNode::Int(inner_node) => {
                 f.debug_struct("Int")
                     .field("token_idx", &inner_node.token_index)
                     .field("tokens", &inner_node.tokens)
-                    .field("buffer", &bytes::Bytes::copy_from_slice(&inner_node.buffer))
+                    .field("buffer", &bytes::Bytes::copy_from_slice(inner_node.bytes()))
                     .finish()
             }
             BdecodeNode::End(inner_node) => {
                 f.debug_struct("End")
                     .field("token_idx", &inner_node.token_index)
                     .field("tokens", &inner_node.tokens)
-                    .field("buffer", &bytes::Bytes::copy_from_slice(&inner_node.buffer))
+                    .field("buffer", &bytes::Bytes::copy_from_slice(inner_node.bytes()))
                     .finish()
             }
         }
@@ -470,15 +980,15 @@ mod tests {
     fn test_print() {
         // {"\x04b": "v\x02", "k2": {"k3": "v3", "k4": 9}, "k5": [7, {"b1": "bb"}], "k6": "v6"}
         let buffer = b"d 2:\x04b 2:v\x02 2:k2 d 2:k3 2:v3 2:k4 i9e e 2:k5 l i7e d 2:b1 2:bb e e 2:k6 2:v6 e"
-            .into_iter()
+            .iter()
             .filter(|v| {
                 **v != b' '
             })
             .cloned()
             .collect::<Vec<_>>();
         let node = BdecodeNode::parse_buffer(buffer).unwrap();
-        println!("{}", &node.to_json_pretty());
-        println!("{}", &node.to_json());
+        println!("{}", node.to_json_pretty());
+        println!("{}", node.to_json());
     }
 
     #[test]
@@ -640,7 +1150,7 @@ mod tests {
         };
         assert_eq!(7, val.token_index());
         assert_eq!(2, val.len());
-        assert_eq!(&vec![8, 9], val.item_indexes.as_ref());
+        assert_eq!(&[8, 9], val.item_indexes());
     }
 
     #[test]
@@ -698,6 +1208,28 @@ mod tests {
         let _ = node.list_item(4);
     }
 
+    #[test]
+    fn test_raw_bytes() {
+        // {"k1": "v1", "k2": [1, -2], "k3": {"k4": 4}}
+        let buffer = "d 2:k1 2:v1 2:k2 l i1e i-2e e 2:k3 d 2:k4 i4e e e".replace(" ", "");
+        let node = BdecodeNode::parse_buffer(buffer.clone().into()).unwrap();
+        assert_eq!(0..buffer.len(), node.byte_range());
+        assert_eq!(buffer.as_bytes(), node.raw_bytes());
+
+        assert_eq!(b"2:v1", node.dict_find(b"k1").unwrap().raw_bytes());
+        assert_eq!(b"li1ei-2ee", node.dict_find(b"k2").unwrap().raw_bytes());
+        assert_eq!(b"i-2e", node.dict_find(b"k2").unwrap().list_item(1).raw_bytes());
+
+        let k3 = node.dict_find(b"k3").unwrap();
+        assert_eq!(26..35, k3.byte_range());
+        assert_eq!(b"d2:k4i4ee", k3.raw_bytes());
+        let BdecodeNode::Dict(k3) = k3 else {
+            panic!("not a Dict node");
+        };
+        assert_eq!(b"d2:k4i4ee", k3.raw_bytes());
+        assert_eq!(b"2:k4", k3.item(0).0.raw_bytes());
+    }
+
     #[test]
     fn test_string_value() {
         let buffer = "11:k1000000012".into();
@@ -710,6 +1242,324 @@ mod tests {
         let buffer = "i19e".into();
         let node = BdecodeNode::parse_buffer(buffer).unwrap();
         assert_eq!(node.as_int().unwrap(), 19);
+
+        let buffer = "i-19e".into();
+        let node = BdecodeNode::parse_buffer(buffer).unwrap();
+        assert_eq!(node.as_int().unwrap(), -19);
+    }
+
+    #[test]
+    fn test_parse_strict() {
+        // {"a": [0, -1, "abc"], "b": {"c": 10}}
+        let buffer = "d 1:a l i0e i-1e 3:abc e 1:b d 1:c i10e e e".replace(" ", "");
+        let node = BdecodeNode::parse_buffer_strict(buffer.into()).unwrap();
+        assert_eq!(2, node.len());
+
+        let err = BdecodeNode::parse_buffer_strict("i-0e".into()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::NegativeZero(0)));
+
+        let err = BdecodeNode::parse_buffer_strict("l i1e i007e e".replace(" ", "").into()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::IntegerLeadingZero(4)));
+
+        let err = BdecodeNode::parse_buffer_strict("i-01e".into()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::IntegerLeadingZero(0)));
+
+        let err = BdecodeNode::parse_buffer_strict("ie".into()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::ExpectedDigit(1)));
+
+        let err = BdecodeNode::parse_buffer_strict("l 1:a 03:abc e".replace(" ", "").into()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::LengthLeadingZero(4)));
+
+        // {"b": 1, "a": 2}
+        let err = BdecodeNode::parse_buffer_strict("d 1:b i1e 1:a i2e e".replace(" ", "").into()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::UnsortedKey(7)));
+
+        // {"a": 1, "ab": {"x": 1, "x": 2}}
+        let buffer = "d 1:a i1e 2:ab d 1:x i1e 1:x i2e e e".replace(" ", "");
+        let err = BdecodeNode::parse_buffer_strict(buffer.into()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::DuplicateKey(18)));
+
+        // 非严格模式下可以正常解析
+        let buffer = "d 1:b i-0e 1:a 03:abc 1:a i007e e".replace(" ", "");
+        let node = BdecodeNode::parse_buffer(buffer.into()).unwrap();
+        assert_eq!(3, node.len());
+    }
+
+    #[test]
+    fn test_parse_prefix() {
+        // BEP 9 ut_metadata 的 data 消息，bencode 头之后是 piece 的原始数据
+        let mut buffer = b"d8:msg_typei1e5:piecei0e10:total_sizei3ee".to_vec();
+        let header_len = buffer.len();
+        buffer.extend_from_slice(b"\x00d4:");
+
+        let (node, consumed) = BdecodeNode::parse_prefix(buffer.clone(), None, None).unwrap();
+        assert_eq!(header_len, consumed);
+        assert_eq!(Some(3), node.dict_find_as_int(b"total_size"));
+        assert_eq!(b"\x00d4:", &node.buffer()[consumed..]);
+        assert_eq!(0..header_len, node.byte_range());
+
+        // 首尾相连的多个消息
+        let (node, consumed) = BdecodeNode::parse_prefix(b"i1e3:abc".to_vec(), None, None).unwrap();
+        assert_eq!(3, consumed);
+        assert_eq!(1, node.as_int().unwrap());
+        let (node, consumed) = BdecodeNode::parse_prefix(b"3:abci1e".to_vec(), None, None).unwrap();
+        assert_eq!(5, consumed);
+        assert_eq!(b"abc", node.as_str().as_ref());
+
+        let (_, consumed) = BdecodeNode::parse_prefix(b"le".to_vec(), None, None).unwrap();
+        assert_eq!(2, consumed);
+
+        // parse 忽略之后的数据，parse_exact 返回错误
+        assert!(BdecodeNode::parse_buffer(buffer.clone()).is_ok());
+        let err = BdecodeNode::parse_buffer_exact(buffer).unwrap_err();
+        assert!(matches!(err, BdecodeError::TrailingData(pos) if pos == header_len));
+        let err = BdecodeNode::parse_buffer_exact(b"i1ee".to_vec()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::TrailingData(3)));
+        assert!(BdecodeNode::parse_buffer_exact(b"d1:ai1ee".to_vec()).is_ok());
+    }
+
+    #[test]
+    fn test_parse_from() {
+        // bytes::Bytes 直接作为 buffer, 不会复制数据
+        let buffer = bytes::Bytes::from_static(b"d1:ad1:bi1ee1:cl3:abcee");
+        let node = BdecodeNode::parse_from(buffer.clone(), None, None).unwrap();
+        assert_eq!(buffer.as_ptr(), node.buffer_bytes().as_ptr());
+
+        let a = node.dict_find(b"a").unwrap();
+        assert_eq!(Some(1), a.dict_find_as_int(b"b"));
+        assert_eq!(b"abc", node.dict_find(b"c").unwrap().list_item_as_str(0).as_ref());
+        assert_eq!(b"d1:bi1ee", a.raw_bytes());
+        assert_eq!(r#"{ "a": { "b": 1 }, "c": ["abc"] }"#, node.to_json());
+
+        // 借用调用方的 &[u8]
+        let buffer = b"l4:spami42ee".to_vec();
+        let node = BdecodeNode::parse_from(buffer.as_slice(), None, None).unwrap();
+        assert_eq!(buffer.as_ptr(), node.buffer_bytes().as_ptr());
+        assert_eq!(42, node.list_item_as_int(1).unwrap());
+
+        let err = BdecodeNode::parse_exact_from(&b"i1ee"[..], None, None).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::TrailingData(3)));
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_parse_mmap() {
+        use std::io::Write;
+
+        let dir = crate::test_utils::temp_dir("mmap");
+        let path = dir.join("test.torrent");
+        let buffer = b"d8:announce3:url4:infod6:lengthi1024e4:name4:testee";
+        std::fs::File::create(&path).unwrap().write_all(buffer).unwrap();
+
+        let file = std::fs::File::open(&path).unwrap();
+        let node = unsafe { BdecodeNode::parse_mmap(&file, None, None) }.unwrap();
+        drop(file);
+
+        assert_eq!(buffer, node.buffer_bytes());
+        assert_eq!(b"url", node.dict_find_as_str(b"announce").unwrap().as_ref());
+        let info = node.dict_find(b"info").unwrap();
+        assert_eq!(Some(1024), info.dict_find_as_int(b"length"));
+        assert_eq!(b"d6:lengthi1024e4:name4:teste", info.raw_bytes());
+
+        // 空文件
+        std::fs::File::create(&path).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        let err = unsafe { BdecodeNode::parse_mmap(&file, None, None) }.unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::UnexpectedEof(0)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_wide_tokens() {
+        // 字符串长度有 8 位数字，超过了 BdecodeToken 中 header_size 的范围
+        let len = 10_000_000;
+        let mut buffer = format!("d4:datal{len}:").into_bytes();
+        buffer.resize(buffer.len() + len, b'x');
+        buffer.extend_from_slice(b"i42ee4:name4:teste");
+
+        let node = BdecodeNode::parse_buffer_exact(buffer.clone()).unwrap();
+        let BdecodeNode::Dict(dict) = &node else { panic!("not a Dict node") };
+        assert!(dict.tokens.is_wide());
+
+        let data = node.dict_find(b"data").unwrap();
+        assert_eq!(len, data.list_item_as_str(0).len());
+        assert_eq!(42, data.list_item_as_int(1).unwrap());
+        assert_eq!(b"test", node.dict_find_as_str(b"name").unwrap().as_ref());
+        assert_eq!(0..buffer.len(), node.byte_range());
+
+        // offset 超过 BUFFER_MAX_OFFSET, 字符串的内容全部为 0, 解析时不会访问，所以不会占用实际的内存
+        let len = limits::BUFFER_MAX_OFFSET + 16;
+        let header = format!("l{len}:");
+        let mut buffer = vec![0u8; header.len() + len + 5];
+        buffer[..header.len()].copy_from_slice(header.as_bytes());
+        buffer[header.len() + len..].copy_from_slice(b"i42ee");
+
+        let node = BdecodeNode::parse(buffer, None, None).unwrap();
+        let BdecodeNode::List(list) = &node else { panic!("not a List node") };
+        assert!(list.tokens.is_wide());
+        assert_eq!(len, node.list_item_as_str(0).len());
+        assert_eq!(42, node.list_item_as_int(1).unwrap());
+        assert_eq!(header.len() + len, node.list_item(1).offset());
+
+        // 普通的数据仍然使用 BdecodeToken
+        let node = BdecodeNode::parse_buffer(b"d4:name4:teste".to_vec()).unwrap();
+        let BdecodeNode::Dict(dict) = &node else { panic!("not a Dict node") };
+        assert!(!dict.tokens.is_wide());
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let parse = |buffer: &[u8], options: ParseOptions| BdecodeNode::parse_with_options(buffer.to_vec(), &options);
+
+        let err = parse(b"lli1eee", ParseOptions::new().with_depth_limit(2)).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::DepthExceeded { limit: 2, position: 2 }));
+        assert!(parse(b"lli1eee", ParseOptions::new().with_depth_limit(3)).is_ok());
+
+        // 报告配置的 token 数量限制，而不是默认值
+        let err = parse(b"li1ei2ei3ee", ParseOptions::new().with_token_limit(3)).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::TokenLimitExceeded { limit: 3, position: 7 }));
+        let err = BdecodeNode::parse(b"li1ei2ei3ee".to_vec(), None, Some(2)).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::TokenLimitExceeded { limit: 2, position: 4 }));
+        // 负数的限制不会被当作不限制，第一个 token 就超出限制
+        for token_limit in [0, -1, i32::MIN] {
+            let err = BdecodeNode::parse(b"i1e".to_vec(), None, Some(token_limit)).unwrap_err();
+            assert!(matches!(err.kind(), BdecodeError::TokenLimitExceeded { limit: 0, position: 0 }));
+        }
+        assert!(BdecodeNode::parse(b"i1e".to_vec(), None, Some(1)).is_ok());
+
+        let err = parse(b"l3:abc4:abcde", ParseOptions::new().with_max_string_len(3)).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::StringLengthExceeded { limit: 3, position: 6 }));
+
+        let err = parse(b"li-123ei1234ee", ParseOptions::new().with_max_int_digits(3)).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::IntegerDigitsExceeded { limit: 3, position: 7 }));
+
+        // dict 的一个 key/value 对为一个 item, 嵌套的 list 单独计数
+        let options = ParseOptions::new().with_max_items(2);
+        assert!(parse(b"d1:ali1ei2ee1:bi2ee", options.clone()).is_ok());
+        let err = parse(b"d1:ai1e1:bi2e1:ci3ee", options.clone()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::ItemCountExceeded { limit: 2, position: 13 }));
+        let err = parse(b"ll1:a1:b1:cee", options).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::ItemCountExceeded { limit: 2, position: 8 }));
+
+        let options = ParseOptions::new().with_trailing_data(TrailingDataPolicy::Reject);
+        assert!(parse(b"i1e", options.clone()).is_ok());
+        let err = parse(b"i1ei2e", options).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::TrailingData(3)));
+        assert!(parse(b"i1ei2e", ParseOptions::new()).is_ok());
+
+        let err = parse(b"d1:bi1e1:ai2ee", ParseOptions::new().with_strict(true)).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::UnsortedKey(7)));
+
+        let err = BdecodeError::StringLengthExceeded { limit: 3, position: 6 };
+        assert_eq!("String length limit '3' exceeded at position '6' .", err.to_string());
+    }
+
+    #[test]
+    fn test_dict_find_large() {
+        let keys: Vec<String> = (0..1000).map(|i| format!("k{i:04}")).collect();
+        let encode = |keys: &[String]| {
+            let mut buffer = b"d".to_vec();
+            for (i, key) in keys.iter().enumerate() {
+                buffer.extend(format!("{}:{}i{}e", key.len(), key, i).into_bytes());
+            }
+            buffer.push(b'e');
+            buffer
+        };
+
+        // key 按升序排列时使用二分查找
+        let node = BdecodeNode::parse_buffer(encode(&keys)).unwrap();
+        let BdecodeNode::Dict(dict) = &node else { panic!("not a Dict node") };
+        assert!(dict.tokens.keys_sorted(0));
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(Some(i as i64), node.dict_find_as_int(key.as_bytes()));
+        }
+        assert!(dict.find(b"k").is_none());
+        assert!(dict.find(b"k1000").is_none());
+
+        // key 没有排序并且有重复的 key 时，线性查找和 hash 索引都返回第一个
+        let mut unsorted = keys.clone();
+        unsorted.reverse();
+        unsorted.push("k0500".to_string());
+        let node = BdecodeNode::parse_buffer(encode(&unsorted)).unwrap();
+        let BdecodeNode::Dict(dict) = &node else { panic!("not a Dict node") };
+        assert!(!dict.tokens.keys_sorted(0));
+        assert_eq!(Some(499), dict.find(b"k0500").unwrap().as_int().ok());
+        assert_eq!(Some(499), dict.find_indexed(b"k0500").unwrap().as_int().ok());
+        assert!(dict.find_indexed(b"k1000").is_none());
+        for (i, key) in unsorted.iter().take(1000).enumerate() {
+            assert_eq!(Some(i as i64), dict.clone().find(key.as_bytes()).unwrap().as_int().ok());
+        }
+
+        // hash 索引保存在共享的 token 集合中，重新获取的 dict 节点也使用该索引
+        let mut buffer = b"d1:x".to_vec();
+        buffer.extend(encode(&unsorted));
+        buffer.push(b'e');
+        let node = BdecodeNode::parse_buffer(buffer).unwrap();
+        assert!(node.dict_find(b"x").unwrap().try_as_dict().unwrap().find_indexed(b"k0001").is_some());
+        let BdecodeNode::Dict(inner) = node.dict_find(b"x").unwrap() else { panic!("not a Dict node") };
+        assert!(inner.tokens.key_index(inner.token_index as usize).is_some());
+        assert!(node.try_as_dict().unwrap().tokens.key_index(0).is_none());
+        assert_eq!(Some(499), node.dict_find(b"x").unwrap().dict_find_as_int(b"k0500"));
+
+        // 嵌套的 dict 各自记录 key 是否有序
+        let node = BdecodeNode::parse_buffer(b"d1:ad1:bi1e1:ai2ee1:bd1:ai1e1:ai2eee".to_vec()).unwrap();
+        let BdecodeNode::Dict(dict) = &node else { panic!("not a Dict node") };
+        assert!(dict.tokens.keys_sorted(0));
+        assert!(!dict.tokens.keys_sorted(2));
+        assert!(!dict.tokens.keys_sorted(9));
+        assert!(!BdecodeNode::parse_buffer(b"d1:ai1e1:ai2ee".to_vec()).unwrap().try_as_dict().unwrap().tokens.keys_sorted(0));
+    }
+
+    #[test]
+    fn test_try_accessors() {
+        let node = BdecodeNode::parse_buffer(b"d1:ai1e1:bl3:abci2ee1:cd1:xi3ee1:d3:xyze".to_vec()).unwrap();
+        assert!(node.is_dict() && !node.is_list() && !node.is_str() && !node.is_int());
+
+        assert_eq!(4, node.try_len().unwrap());
+        assert_eq!(Some(1), node.try_dict_find_as_int(b"a").unwrap());
+        assert_eq!(b"xyz", node.try_dict_find_as_str(b"d").unwrap().unwrap().as_ref());
+        assert_eq!(2, node.try_dict_find_as_list(b"b").unwrap().unwrap().len());
+        let dict = node.try_dict_find_as_dict(b"c").unwrap().unwrap();
+        assert_eq!(3, dict[b"x".as_slice()].try_as_int().unwrap());
+        assert!(node.try_dict_find(b"z").unwrap().is_none());
+        assert!(node.try_dict_find_as_str(b"z").unwrap().is_none());
+        let (key, value) = node.try_dict_item(0).unwrap();
+        assert_eq!(b"a", key.try_as_str().unwrap().as_ref());
+        assert_eq!(1, value.try_as_int().unwrap());
+
+        let list = node.try_dict_find(b"b").unwrap().unwrap();
+        assert!(list.is_list());
+        assert_eq!(b"abc", list.try_list_item_as_str(0).unwrap().as_ref());
+        assert_eq!(2, list.try_list_item_as_int(1).unwrap());
+
+        // 类型不匹配
+        let err = node.try_dict_find_as_str(b"a").unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::TypeMismatch { expected: "str", found: "int", offset: 4 }));
+        let err = node.try_dict_find_as_int(b"b").unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::TypeMismatch { expected: "int", found: "list", offset: 10 }));
+        assert!(matches!(node.try_as_int(), Err(BdecodeError::TypeMismatch { found: "dict", offset: 0, .. })));
+        assert!(matches!(node.try_as_str(), Err(BdecodeError::TypeMismatch { .. })));
+        assert!(matches!(node.try_list_item(0), Err(BdecodeError::TypeMismatch { expected: "list", .. })));
+        assert!(matches!(list.try_dict_find(b"a").unwrap_err().kind(), BdecodeError::TypeMismatch { expected: "dict", .. }));
+        assert!(matches!(list.try_list_item_as_int(0).unwrap_err().kind(), BdecodeError::TypeMismatch { expected: "int", .. }));
+        assert!(matches!(list.try_list_item_as_str(1).unwrap_err().kind(), BdecodeError::TypeMismatch { expected: "str", .. }));
+        let int = node.try_dict_find(b"a").unwrap().unwrap();
+        assert!(matches!(int.try_len().unwrap_err().kind(), BdecodeError::TypeMismatch { expected: "list or dict", .. }));
+
+        // 索引越界
+        let err = list.try_list_item(2).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::IndexOutOfRange { index: 2, len: 2, offset: 10 }));
+        assert!(matches!(node.try_dict_item(4), Err(BdecodeError::IndexOutOfRange { index: 4, len: 4, offset: 0 })));
+        assert_eq!(
+            "Index '2' out of range for length '2' at position '10' . (path 'b')",
+            err.to_string()
+        );
+
+        // 错误中附带出错节点的路径
+        assert_eq!("b[1]", list.try_list_item_as_str(1).unwrap_err().path().unwrap().to_string());
+        assert_eq!("c", node.try_dict_find_as_list(b"c").unwrap_err().path().unwrap().to_string());
+        assert!(node.try_as_int().unwrap_err().path().is_none());
     }
 
     #[test]
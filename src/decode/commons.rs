@@ -1,19 +1,32 @@
 /// 阈值常量
 pub mod limits {
-    /// buffer 的最大长度，也就是 Token 中 offset 的最大值。
+    /// BdecodeToken 中 offset 的最大值，超过时使用 WideBdecodeToken 。
     pub const BUFFER_MAX_OFFSET: usize = (1 << 29) - 1;
     
-    /// 下一个 Token 相对位置的最大值。
+    /// BdecodeToken 中下一个 Token 相对位置的最大值，超过时使用 WideBdecodeToken 。
     pub const MAX_NEXT_ITEM: usize = (1 << 29) - 1;
 
-    ///指 Dict 中组成 key 字符串的最大长度。
+    /// BdecodeToken 中字符串长度的位数的最大值，超过时使用 WideBdecodeToken 。
     pub const MAX_HEADER_SIZE: usize = 7;
 
+    /// WideBdecodeToken 中下一个 Token 相对位置的最大值。
+    pub const WIDE_MAX_NEXT_ITEM: usize = u32::MAX as usize;
+
+    /// 解析结果中 token 数量的最大值，包括最后的虚拟 end token 。
+    /// StackFrame 只有 31 位保存 token 的索引，超过时返回 LimitExceeded, 所以 ChildIndexes 中的 u32 索引也不会溢出。
+    pub const MAX_TOKENS: usize = (1 << 31) - 1;
+
+    /// WideBdecodeToken 中字符串长度的位数的最大值。
+    pub const WIDE_MAX_HEADER_SIZE: usize = (1 << 5) - 1;
+
     /// 解析时 dict 和 list 的最大嵌套深度限制。
     pub const DEFAULT_DEPTH_LIMIT: usize = 100;
 
     /// 解析时 token 最大数量。
-	pub const DEFAULT_TOKEN_LIMIT: i32 = 1000000;
+	pub const DEFAULT_TOKEN_LIMIT: usize = 1000000;
+
+    /// 解析时整数的最大位数，也就是 i64 的最大位数。
+    pub const DEFAULT_MAX_INT_DIGITS: usize = 20;
 }
 
 pub const IDENT_LEN: usize = 4;
\ No newline at end of file
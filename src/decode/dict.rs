@@ -1,14 +1,21 @@
 use std::{borrow::Cow, collections::HashMap};
 
-use crate::decode::{commons::IDENT_LEN, utils::gen_blanks};
+use crate::{decode::{commons::IDENT_LEN, utils::gen_blanks}, BdecodeError, BdecodeResult};
 
-use super::{token::BdecodeTokenType, utils::escape_string, BdecodeNode, IBdecodeNode, Style};
+use super::{
+    token::{BdecodeTokenType, KeyIndex},
+    utils::{escape_string, str_token_value},
+    BdecodeNode, IBdecodeNode, Style,
+};
+
+/// item 数量不超过该值时直接线性查找，不使用二分查找
+const LINEAR_SEARCH_MAX_LEN: usize = 8;
 
 crate::collective_bdecode_node!(Dict);
 
-impl Dict {
+impl<B: AsRef<[u8]>> Dict<B> {
     /// 获取 dict 中指定索引的节点对(key, value)
-    pub fn item(&self, index: usize) -> (BdecodeNode, BdecodeNode) {
+    pub fn item(&self, index: usize) -> (BdecodeNode<B>, BdecodeNode<B>) {
         assert!(self.token_type() == BdecodeTokenType::Dict);
 
         if index >= self.len() {
@@ -16,12 +23,12 @@ impl Dict {
         }
 
         // get key node
-        let key_token_idx = self.item_indexes[index];
+        let key_token_idx = self.item_indexes()[index];
         if key_token_idx as usize >= self.tokens.len() {
             panic!("index out of range in tokens");
         }
         let key_node = BdecodeNode::new(key_token_idx, self.tokens(), self.buffer.clone());
-        let key_token = &self.tokens[key_token_idx as usize];
+        let key_token = self.tokens.get(key_token_idx as usize);
         
         // get value node
         let val_token_idx = key_token_idx + key_token.next_item();
@@ -30,27 +37,89 @@ impl Dict {
         (key_node, val_node)
     }
 
-    /// 在 dict 中查找 key 对应的 value
-    pub fn find(&self, key: &[u8]) -> Option<BdecodeNode> {
+    /// 与 item() 相同，但索引越界时返回 IndexOutOfRange 错误，而不是 panic
+    pub fn try_item(&self, index: usize) -> BdecodeResult<(BdecodeNode<B>, BdecodeNode<B>)> {
+        if index >= self.len() {
+            return Err(BdecodeError::IndexOutOfRange {
+                index,
+                len: self.len(),
+                offset: self.byte_range().start,
+            }
+            .with_path(self.path()));
+        }
+
+        Ok(self.item(index))
+    }
+
+    /// 在 dict 中查找 key 对应的 value, 有重复的 key 时返回第一个
+    ///
+    /// - 已经通过 find_indexed() 建立了 hash 索引时，使用 hash 索引查找;
+    /// - 解析时 key 严格按升序排列（也就是符合 bencode 规范的数据）时，使用二分查找;
+    /// - 否则线性查找。
+    pub fn find(&self, key: &[u8]) -> Option<BdecodeNode<B>> {
         assert!(self.token_type() == BdecodeTokenType::Dict);
 
-        for token_index in self.item_indexes.as_ref() {
-            let token = &self.tokens[*token_index as usize];
-            assert!(token.node_type() == BdecodeTokenType::Str);
-            let next_offset = self.tokens[(token_index + 1) as usize].offset() as usize;
-            let start = (token.offset() + token.header_size() as u32 + 1) as usize;
+        let key_token_idx = if let Some(key_index) = self.tokens.key_index(self.token_index as usize) {
+            key_index.get(key).copied()
+        } else if self.len() > LINEAR_SEARCH_MAX_LEN && self.tokens.keys_sorted(self.token_index as usize) {
+            self.binary_search(key)
+        } else {
+            self.linear_search(key)
+        };
+
+        key_token_idx.map(|idx| self.value_node(idx))
+    }
 
-            if &self.buffer[start..next_offset] == key {
-                let val_token_idx = *token_index + token.next_item();
+    /// 与 find() 相同，但第一次调用时为 dict 建立 hash 索引，之后的查找（包括 find() ）都使用该索引
+    ///
+    /// 索引保存在整个文档共享的 token 集合中，之后再次获取的同一个 dict 节点（例如通过 dict_find() ）也会使用该索引。
+    /// 适用于 key 没有排序、又需要多次查找的大 dict. 建立索引需要遍历一次所有的 key, 并复制一份 key, 占用与 key 的总长度相当的内存。
+    pub fn find_indexed(&self, key: &[u8]) -> Option<BdecodeNode<B>> {
+        assert!(self.token_type() == BdecodeTokenType::Dict);
 
-                return Some(BdecodeNode::new(val_token_idx, self.tokens(), self.buffer.clone()));
+        let key_index = self.tokens.key_index_or_build(self.token_index as usize, || {
+            let mut key_index = KeyIndex::with_capacity(self.len());
+            for token_index in self.item_indexes().iter() {
+                key_index.entry(self.key_bytes(*token_index).to_vec()).or_insert(*token_index);
             }
-        }
 
-        None
+            key_index
+        });
+
+        key_index.get(key).map(|idx| self.value_node(*idx))
     }
 
-    pub fn find_as_str(&self, key: &[u8]) -> Option<Cow<[u8]>> {
+    /// key token 对应的 key 的值
+    fn key_bytes(&self, key_token_idx: u32) -> &[u8] {
+        assert!(self.tokens.node_type(key_token_idx as usize) == BdecodeTokenType::Str);
+
+        str_token_value(&self.tokens, self.bytes(), key_token_idx as usize)
+    }
+
+    /// key token 之后的 value 节点
+    fn value_node(&self, key_token_idx: u32) -> BdecodeNode<B> {
+        let val_token_idx = key_token_idx + self.tokens.get(key_token_idx as usize).next_item();
+
+        BdecodeNode::new(val_token_idx, self.tokens(), self.buffer.clone())
+    }
+
+    fn linear_search(&self, key: &[u8]) -> Option<u32> {
+        self.item_indexes()
+            .iter()
+            .find(|token_index| self.key_bytes(**token_index) == key)
+            .copied()
+    }
+
+    /// 只能用于 key 严格按升序排列的 dict
+    fn binary_search(&self, key: &[u8]) -> Option<u32> {
+        let item_indexes = self.item_indexes();
+        item_indexes
+            .binary_search_by(|token_index| self.key_bytes(*token_index).cmp(key))
+            .ok()
+            .map(|i| item_indexes[i])
+    }
+
+    pub fn find_as_str(&self, key: &[u8]) -> Option<Cow<'_, [u8]>> {
         let node = self.find(key);
 
         if let Some(node) = node {
@@ -76,7 +145,7 @@ impl Dict {
         None
     }
 
-    pub fn find_as_list(&self, key: &[u8]) -> Option<Vec<BdecodeNode>> {
+    pub fn find_as_list(&self, key: &[u8]) -> Option<Vec<BdecodeNode<B>>> {
         let node = self.find(key);
 
         if let Some(node) = node {
@@ -96,10 +165,8 @@ impl Dict {
         None
     }
 
-    pub fn find_as_dict(&self, key: &[u8]) -> Option<HashMap<Cow<[u8]>, BdecodeNode>> {
-        let Some(node) = self.find(key) else {
-            return None;
-        };
+    pub fn find_as_dict(&self, key: &[u8]) -> Option<HashMap<Cow<'_, [u8]>, BdecodeNode<B>>> {
+        let node = self.find(key)?;
 
         let mut node_map = HashMap::new();
         let BdecodeNode::Dict(node) = node else { return None };
@@ -138,11 +205,11 @@ impl Dict {
             }
 
             if i < len - 1 { 
-                sb.push_str(","); 
+                sb.push(','); 
                 if Style::Compact == style {
-                    sb.push_str(" "); 
+                    sb.push(' '); 
                 } else {
-                    sb.push_str("\n");
+                    sb.push('\n');
                 }
             }
         }
@@ -3,7 +3,7 @@ use super::Style;
 
 crate::primitive_bdecode_node!(End);
 
-impl End {
+impl<B: AsRef<[u8]>> End<B> {
 
     pub fn to_json_with_style(&self, _style: Style) -> String {
         "".to_string()
@@ -0,0 +1,193 @@
+use std::mem;
+
+use crate::BdecodeResult;
+
+use super::{BdecodeNode, ParseOptions, ParseState};
+
+/// IncrementalParser::push() 的结果
+#[derive(Debug)]
+pub enum ParseStatus {
+    /// 数据还不完整，需要继续 push
+    NeedMore,
+
+    /// 解析出了一个完整的值，以及本次 push 的 chunk 中属于该值的字节数
+    Complete(BdecodeNode, usize),
+}
+
+/// 增量解析器，用于数据分多次到达的场景，例如从 TCP 连接中读取的 bencode 消息
+///
+/// 每次 push 时只解析新到达的数据，token 和 stack 等解析状态保存在多次调用之间，
+/// 各项限制与 BdecodeNode::parse_with_options() 相同，但不检查 trailing data: 之后的数据属于下一个消息。
+///
+/// 返回 Complete 后解析器会重置，chunk 中剩余的数据（ `chunk[consumed..]` ）属于下一个消息，需要再次 push.
+/// 错误信息中的位置是相对于当前消息开头的位置，出错后解析器也会重置。
+pub struct IncrementalParser {
+    options: ParseOptions,
+
+    /// 当前消息已经收到的数据
+    buffer: Vec<u8>,
+
+    state: ParseState,
+}
+
+impl IncrementalParser {
+    pub fn new(depth_limit: Option<usize>, token_limit: Option<i32>) -> Self {
+        Self::with_options(ParseOptions::from_limits(depth_limit, token_limit))
+    }
+
+    /// 按照解析选项创建增量解析器，参见 ParseOptions
+    pub fn with_options(options: ParseOptions) -> Self {
+        Self {
+            state: ParseState::new(&options),
+            options,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// 追加一段数据并继续解析
+    pub fn push(&mut self, chunk: &[u8]) -> BdecodeResult<ParseStatus> {
+        let prev_len = self.buffer.len();
+        self.buffer.extend_from_slice(chunk);
+        match self.state.run(&self.buffer, true) {
+            Ok(false) => Ok(ParseStatus::NeedMore),
+            Ok(true) => {
+                let state = mem::replace(&mut self.state, ParseState::new(&self.options));
+                let mut buffer = mem::take(&mut self.buffer);
+
+                // 去掉属于下一个消息的数据
+                buffer.truncate(state.consumed());
+                let (node, consumed) = state.finish(buffer);
+
+                Ok(ParseStatus::Complete(node, consumed - prev_len))
+            }
+            Err(e) => {
+                self.reset();
+                Err(e)
+            }
+        }
+    }
+
+    /// 丢弃当前消息已经收到的数据
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.state = ParseState::new(&self.options);
+    }
+
+    /// 当前消息已经收到的字节数
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+impl Default for IncrementalParser {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BdecodeError;
+
+    use super::*;
+
+    /// 逐字节 push, 返回 Complete 时 push 的次数
+    fn push_bytes(parser: &mut IncrementalParser, buffer: &[u8]) -> (BdecodeNode, usize) {
+        for (i, c) in buffer.iter().enumerate() {
+            match parser.push(&[*c]).unwrap() {
+                ParseStatus::NeedMore => continue,
+                ParseStatus::Complete(node, consumed) => {
+                    assert_eq!(1, consumed);
+                    return (node, i + 1);
+                }
+            }
+        }
+
+        panic!("not complete")
+    }
+
+    #[test]
+    fn test_push_byte_by_byte() {
+        let buffer = "d 1:a d 1:b i-12e e 3:cde 10:0123456789 1:x l i1e l e e e".replace(" ", "").into_bytes();
+        let mut parser = IncrementalParser::default();
+
+        let (node, pushed) = push_bytes(&mut parser, &buffer);
+        assert_eq!(buffer.len(), pushed);
+        assert_eq!(0, parser.buffered_len());
+        assert_eq!(node.to_json(), BdecodeNode::parse_buffer(buffer.clone()).unwrap().to_json());
+        assert_eq!(buffer.as_slice(), node.raw_bytes());
+
+        // 解析器重置后可以继续解析下一个消息
+        let (node, pushed) = push_bytes(&mut parser, b"i42e");
+        assert_eq!(4, pushed);
+        assert_eq!(42, node.as_int().unwrap());
+    }
+
+    #[test]
+    fn test_push_chunks() {
+        let mut parser = IncrementalParser::default();
+
+        assert!(matches!(parser.push(b"").unwrap(), ParseStatus::NeedMore));
+        assert!(matches!(parser.push(b"d3:key5:va").unwrap(), ParseStatus::NeedMore));
+        assert_eq!(10, parser.buffered_len());
+
+        // chunk 中包含下一个消息的开头
+        let chunk = b"luee4:sp";
+        let ParseStatus::Complete(node, consumed) = parser.push(chunk).unwrap() else {
+            panic!("not complete")
+        };
+        assert_eq!(4, consumed);
+        assert_eq!(b"value", node.dict_find_as_str(b"key").unwrap().as_ref());
+        assert_eq!(b"d3:key5:valuee", node.buffer().as_slice());
+
+        assert!(matches!(parser.push(&chunk[consumed..]).unwrap(), ParseStatus::NeedMore));
+        let ParseStatus::Complete(node, consumed) = parser.push(b"am").unwrap() else {
+            panic!("not complete")
+        };
+        assert_eq!(2, consumed);
+        assert_eq!(b"spam", node.as_str().as_ref());
+    }
+
+    #[test]
+    fn test_push_errors() {
+        let mut parser = IncrementalParser::default();
+        assert!(matches!(parser.push(b"d1:a").unwrap(), ParseStatus::NeedMore));
+        let err = parser.push(b"x").unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::ExpectedDigit(4)));
+
+        // 出错后解析器会重置
+        assert_eq!(0, parser.buffered_len());
+        assert!(matches!(parser.push(b"i1e").unwrap(), ParseStatus::Complete(..)));
+
+        assert!(matches!(parser.push(b"i12a").unwrap_err(), BdecodeError::ExpectedDigit(_)));
+        let err = parser.push(b"12x").unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::ExpectedDigit(2)));
+
+        // 与 parse 相同的深度限制
+        let mut parser = IncrementalParser::new(Some(2), None);
+        assert!(matches!(parser.push(b"ll").unwrap(), ParseStatus::NeedMore));
+        assert!(matches!(
+            parser.push(b"l").unwrap_err().kind(),
+            BdecodeError::DepthExceeded { limit: 2, position: 2 }
+        ));
+
+        // 与 parse 相同的 token 数量限制
+        let mut parser = IncrementalParser::new(None, Some(3));
+        assert!(matches!(parser.push(b"li1ei2e").unwrap(), ParseStatus::NeedMore));
+        assert!(matches!(
+            parser.push(b"i3e").unwrap_err().kind(),
+            BdecodeError::TokenLimitExceeded { limit: 3, position: 7 }
+        ));
+
+        let mut parser = IncrementalParser::with_options(ParseOptions::new().with_max_string_len(3));
+        assert!(matches!(parser.push(b"l3:abc").unwrap(), ParseStatus::NeedMore));
+        let err = parser.push(b"4:abcde").unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::StringLengthExceeded { limit: 3, position: 6 }));
+
+        // 收到字符串长度后立即检查，不等待整个字符串
+        assert!(matches!(parser.push(b"l500000").unwrap(), ParseStatus::NeedMore));
+        let err = parser.push(b"000:").unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::StringLengthExceeded { limit: 3, position: 1 }));
+        assert_eq!(0, parser.buffered_len());
+    }
+}
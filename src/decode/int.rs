@@ -1,39 +1,53 @@
 
-use super::{token::BdecodeTokenType, utils::parse_uint, BdecodeResult, IBdecodeNode, Style};
+use crate::BdecodeError;
+
+use super::{token::BdecodeTokenType, BdecodeResult, IBdecodeNode, Style};
 
 crate::primitive_bdecode_node!(Int);
 
-impl Int {
-    /// 获取当前节点的整数值
+impl<B: AsRef<[u8]>> Int<B> {
+    /// 获取当前节点的整数值，超出 i64 的范围时返回 Overflow 错误
     pub fn value(&self) -> BdecodeResult<i64> {
         assert!(self.token_type() == BdecodeTokenType::Int);
 
-        let token_idx = self.token_index as usize;
-        let t = &self.tokens[token_idx];
-        let size = self.tokens[token_idx + 1].offset() - t.offset();
-
-        // +1 is to skip the 'i'
-        let start = t.offset() + 1;
-        let mut val = 0;
-        let mut negative = false;
-
-        if  self.buffer[start as usize] == b'-' {
-            negative = true;
+        let text = self.text();
+        let (negative, digits) = match text.split_first() {
+            Some((b'-', digits)) => (true, digits),
+            _ => (false, text),
+        };
+
+        // 先按 u64 计算绝对值，这样 i64::MIN 的绝对值也不会溢出
+        let overflow = || BdecodeError::Overflow(String::from_utf8_lossy(text).into_owned());
+        let mut magnitude: u64 = 0;
+        for c in digits {
+            magnitude = magnitude
+                .checked_mul(10)
+                .and_then(|m| m.checked_add((c - b'0') as u64))
+                .ok_or_else(overflow)?;
         }
 
-        let end = parse_uint(self.buffer.as_ref(), start as usize, b'e', &mut val)?;
+        let val = if negative {
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        };
 
-        assert!(end < (start + size) as usize);
+        val.ok_or_else(overflow)
+    }
 
-        if negative {
-            Ok(-val)
-        } else {
-            Ok(val)
-        }
+    /// 'i' 和 'e' 之间的字符串，解析时已经检查过只包含数字和开头的 '-'
+    fn text(&self) -> &[u8] {
+        let range = self.byte_range();
+
+        &self.bytes()[range.start + 1..range.end - 1]
     }
 
     pub fn to_json_with_style(&self, _style: Style) -> String {
-        self.value().expect("parse to int failed").to_string()
+        // 超出 i64 范围的整数原样输出
+        match self.value() {
+            Ok(val) => val.to_string(),
+            Err(_) => String::from_utf8_lossy(self.text()).into_owned(),
+        }
     }
 
 }
\ No newline at end of file
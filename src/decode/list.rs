@@ -2,14 +2,16 @@ use std::borrow::Cow;
 
 use crate::decode::{commons::IDENT_LEN, token::BdecodeTokenType};
 
+use crate::BdecodeError;
+
 use super::{utils::gen_blanks, BdecodeNode, BdecodeResult, IBdecodeNode, Style};
 
 crate::collective_bdecode_node!(List);
 
-impl List {
+impl<B: AsRef<[u8]>> List<B> {
 
     /// 获取 list 中指定索引的节点
-    pub fn item(&self, index: usize) -> BdecodeNode {
+    pub fn item(&self, index: usize) -> BdecodeNode<B> {
         assert!(self.token_type() == BdecodeTokenType::List);
 
         if index >= self.len() {
@@ -17,15 +19,29 @@ impl List {
             panic!("index out of range");
         }
 
-        let token_idx = self.item_indexes[index];
+        let token_idx = self.item_indexes()[index];
         BdecodeNode::new(token_idx, self.tokens.clone(), self.buffer.clone())
     }
 
+    /// 与 item() 相同，但索引越界时返回 IndexOutOfRange 错误，而不是 panic
+    pub fn try_item(&self, index: usize) -> BdecodeResult<BdecodeNode<B>> {
+        if index >= self.len() {
+            return Err(BdecodeError::IndexOutOfRange {
+                index,
+                len: self.len(),
+                offset: self.byte_range().start,
+            }
+            .with_path(self.path()));
+        }
+
+        Ok(self.item(index))
+    }
+
     pub fn as_int(&self, index: usize) -> BdecodeResult<i64> {
         self.item(index).as_int()
     }
 
-    pub fn as_str(&self, index: usize) -> Cow<[u8]> {
+    pub fn as_str(&self, index: usize) -> Cow<'_, [u8]> {
         let node = self.item(index);
         let val = node.as_str();
 
@@ -51,11 +67,11 @@ impl List {
             }
 
             if i < len - 1 { 
-                sb.push_str(","); 
+                sb.push(','); 
                 if Style::Compact == style {
-                    sb.push_str(" "); 
+                    sb.push(' '); 
                 } else {
-                    sb.push_str("\n");
+                    sb.push('\n');
                 }
             }
         }
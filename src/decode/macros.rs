@@ -2,23 +2,33 @@
 #[macro_export]
 macro_rules! primitive_bdecode_node {
     ($node_name:ident) => {
-        #[derive(Debug, Clone)]
-        pub struct $node_name {
+        #[derive(Debug)]
+        pub struct $node_name<B = Vec<u8>> {
             /// 当前节点在 tokens 中的索引
             /// 0 - root 节点值; -1 - 未初始化
             pub token_index: u32,
 
             /// 解析后的 token 集合
-            pub tokens: std::sync::Arc<Vec<super::token::BdecodeToken>>,
+            pub tokens: std::sync::Arc<super::token::BdecodeTokens>,
 
             /// 存放解析前字符串的 buffer
-            pub buffer: std::sync::Arc<Vec<u8>>,
+            pub buffer: std::sync::Arc<B>,
         }
 
-        impl $node_name {
+        impl<B> Clone for $node_name<B> {
+            fn clone(&self) -> Self {
+                Self {
+                    token_index: self.token_index,
+                    tokens: self.tokens.clone(),
+                    buffer: self.buffer.clone(),
+                }
+            }
+        }
+
+        impl<B: AsRef<[u8]>> $node_name<B> {
             pub fn new(
-                buffer: std::sync::Arc<Vec<u8>>,
-                tokens: std::sync::Arc<Vec<super::token::BdecodeToken>>,
+                buffer: std::sync::Arc<B>,
+                tokens: std::sync::Arc<super::token::BdecodeTokens>,
                 token_index: u32,
             ) -> Self {
                 Self {
@@ -27,14 +37,34 @@ macro_rules! primitive_bdecode_node {
                     token_index,
                 }
             }
+
+            /// 当前节点在 buffer 中的范围
+            pub fn byte_range(&self) -> std::ops::Range<usize> {
+                super::utils::token_byte_range(&self.tokens, self.token_index as usize)
+            }
+
+            /// 当前节点在 buffer 中对应的原始字节
+            pub fn raw_bytes(&self) -> &[u8] {
+                &self.bytes()[self.byte_range()]
+            }
+
+            /// 当前节点从 root 开始的路径
+            pub fn path(&self) -> super::BdecodePath {
+                super::path::token_path(&self.tokens, self.bytes(), self.token_index as usize)
+            }
+
+            /// buffer 中的全部字节
+            pub(crate) fn bytes(&self) -> &[u8] {
+                (*self.buffer).as_ref()
+            }
         }
 
-        impl super::IBdecodeNode for $node_name {
+        impl<B> super::IBdecodeNode for $node_name<B> {
             fn token_index(&self) -> usize {
                 self.token_index as usize
             }
 
-            fn tokens(&self) -> std::sync::Arc<Vec<super::token::BdecodeToken>> {
+            fn tokens(&self) -> std::sync::Arc<super::token::BdecodeTokens> {
                 self.tokens.clone()
             }
         }
@@ -44,60 +74,97 @@ macro_rules! primitive_bdecode_node {
 #[macro_export]
 macro_rules! collective_bdecode_node {
     ($node_name:ident) => {
-        #[derive(Debug, Clone)]
-        pub struct $node_name {
+        #[derive(Debug)]
+        pub struct $node_name<B = Vec<u8>> {
             /// 当前节点在 tokens 中的索引
             /// 0 - root 节点值; -1 - 未初始化
             pub token_index: u32,
 
             /// 解析后的 token 集合
-            pub tokens: std::sync::Arc<Vec<super::token::BdecodeToken>>,
+            pub tokens: std::sync::Arc<super::token::BdecodeTokens>,
 
             /// 存放解析前字符串的 buffer
-            pub buffer: std::sync::Arc<Vec<u8>>,
-
-            /// 存放 list 和 map 中 item 的对应的 token 索引集合
-            pub item_indexes: std::sync::Arc<Vec<u32>>,
-            // pub item_begin_len: Option<(u32, u32)>,
+            pub buffer: std::sync::Arc<B>,
 
             /// list 和 map 中 item 的数量
             len: usize,
         }
 
-        impl $node_name {
+        impl<B> Clone for $node_name<B> {
+            fn clone(&self) -> Self {
+                Self {
+                    token_index: self.token_index,
+                    tokens: self.tokens.clone(),
+                    buffer: self.buffer.clone(),
+                    len: self.len,
+                }
+            }
+        }
+
+        impl<B: AsRef<[u8]>> $node_name<B> {
             pub fn new(
-                buffer: std::sync::Arc<Vec<u8>>,
-                tokens: std::sync::Arc<Vec<super::token::BdecodeToken>>,
+                buffer: std::sync::Arc<B>,
+                tokens: std::sync::Arc<super::token::BdecodeTokens>,
                 token_index: u32,
-                item_indexes: std::sync::Arc<Vec<u32>>,
-                len: usize,
             ) -> Self {
+                // 子节点索引表在整个文档中共享，这里不需要遍历子节点
+                let len = tokens.child_indexes(token_index as usize).len();
+
                 Self {
                     buffer,
                     tokens,
                     token_index,
-                    item_indexes,
                     len,
                 }
             }
 
             /// 获取当前 list or dict 节点的长度
             pub fn len(&self) -> usize {
-                use crate::IBdecodeNode;
+                use $crate::IBdecodeNode;
                 use super::token::BdecodeTokenType::*;
 
                 assert!(matches!(self.token_type(), Dict | List));
 
                 self.len
             }
+
+            /// 当前 list or dict 节点是否为空
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// list 中 item 或者 dict 中 key 对应的 token 索引集合
+            pub fn item_indexes(&self) -> &[u32] {
+                self.tokens.child_indexes(self.token_index as usize)
+            }
+
+            /// 当前节点在 buffer 中的范围
+            pub fn byte_range(&self) -> std::ops::Range<usize> {
+                super::utils::token_byte_range(&self.tokens, self.token_index as usize)
+            }
+
+            /// 当前节点在 buffer 中对应的原始字节
+            pub fn raw_bytes(&self) -> &[u8] {
+                &self.bytes()[self.byte_range()]
+            }
+
+            /// 当前节点从 root 开始的路径
+            pub fn path(&self) -> super::BdecodePath {
+                super::path::token_path(&self.tokens, self.bytes(), self.token_index as usize)
+            }
+
+            /// buffer 中的全部字节
+            pub(crate) fn bytes(&self) -> &[u8] {
+                (*self.buffer).as_ref()
+            }
         }
 
-        impl super::IBdecodeNode for $node_name {
+        impl<B> super::IBdecodeNode for $node_name<B> {
             fn token_index(&self) -> usize {
                 self.token_index as usize
             }
 
-            fn tokens(&self) -> std::sync::Arc<Vec<super::token::BdecodeToken>> {
+            fn tokens(&self) -> std::sync::Arc<super::token::BdecodeTokens> {
                 self.tokens.clone()
             }
         }
@@ -1,11 +1,11 @@
-use super::token::{BdecodeToken, BdecodeTokenType};
+use super::token::{BdecodeTokenType, BdecodeTokens};
 
 pub trait IBdecodeNode {
     fn token_index(&self) -> usize;
-    fn tokens(&self) -> std::sync::Arc<Vec<BdecodeToken>>;
+    fn tokens(&self) -> std::sync::Arc<BdecodeTokens>;
     
     /// 获取当前节点的 token 的类型
     fn token_type(&self) -> BdecodeTokenType {
-        self.tokens()[self.token_index() as usize].node_type()
+        self.tokens().node_type(self.token_index())
     }
 }
\ No newline at end of file
@@ -0,0 +1,119 @@
+use super::commons::limits::{DEFAULT_DEPTH_LIMIT, DEFAULT_MAX_INT_DIGITS, DEFAULT_TOKEN_LIMIT};
+
+/// 顶层的值之后还有数据时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailingDataPolicy {
+    /// 忽略之后的数据，与 BdecodeNode::parse() 相同
+    #[default]
+    Ignore,
+
+    /// 返回 TrailingData 错误，与 BdecodeNode::parse_exact() 相同
+    Reject,
+}
+
+/// 解析选项
+///
+/// 每个限制被超出时都会返回对应的错误，错误中包含配置的限制值和超出限制的位置：
+/// - depth_limit: dict 和 list 的最大嵌套深度，超出时返回 DepthExceeded;
+/// - token_limit: token 的最大数量，超出时返回 TokenLimitExceeded;
+/// - max_string_len: 字符串的最大长度，超出时返回 StringLengthExceeded;
+/// - max_int_digits: 整数的最大位数（不包括 '-' ），超出时返回 IntegerDigitsExceeded;
+/// - max_items: 单个 list 或 dict 中 item 的最大数量（dict 的一个 key/value 对为一个 item ），超出时返回 ItemCountExceeded.
+///
+/// ```
+/// use ez_bencoding::{BdecodeNode, ParseOptions, TrailingDataPolicy};
+///
+/// let options = ParseOptions::new()
+///     .with_depth_limit(8)
+///     .with_max_string_len(1024)
+///     .with_strict(true)
+///     .with_trailing_data(TrailingDataPolicy::Reject);
+/// let node = BdecodeNode::parse_with_options(b"d1:ai1ee".as_slice(), &options).unwrap();
+/// assert_eq!(Some(1), node.dict_find_as_int(b"a"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub(crate) depth_limit: usize,
+    pub(crate) token_limit: usize,
+    pub(crate) max_string_len: usize,
+    pub(crate) max_int_digits: usize,
+    pub(crate) max_items: usize,
+    pub(crate) strict: bool,
+    pub(crate) trailing_data: TrailingDataPolicy,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            depth_limit: DEFAULT_DEPTH_LIMIT,
+            token_limit: DEFAULT_TOKEN_LIMIT,
+            max_string_len: usize::MAX,
+            max_int_digits: DEFAULT_MAX_INT_DIGITS,
+            max_items: usize::MAX,
+            strict: false,
+            trailing_data: TrailingDataPolicy::Ignore,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 由 BdecodeNode::parse() 等函数的位置参数生成选项，None 表示使用默认值
+    pub(crate) fn from_limits(depth_limit: Option<usize>, token_limit: Option<i32>) -> Self {
+        let mut options = Self::new();
+        if let Some(depth_limit) = depth_limit {
+            options.depth_limit = depth_limit;
+        }
+        if let Some(token_limit) = token_limit {
+            // 与原来的 parse() 相同，0 和负数的限制都会使第一个 token 就超出限制，返回 TokenLimitExceeded
+            options.token_limit = usize::try_from(token_limit).unwrap_or(0);
+        }
+
+        options
+    }
+
+    /// 设置 dict 和 list 的最大嵌套深度，默认为 100
+    pub fn with_depth_limit(mut self, depth_limit: usize) -> Self {
+        self.depth_limit = depth_limit;
+        self
+    }
+
+    /// 设置 token 的最大数量，默认为 1000000
+    pub fn with_token_limit(mut self, token_limit: usize) -> Self {
+        self.token_limit = token_limit;
+        self
+    }
+
+    /// 设置字符串的最大长度，默认不限制
+    pub fn with_max_string_len(mut self, max_string_len: usize) -> Self {
+        self.max_string_len = max_string_len;
+        self
+    }
+
+    /// 设置整数的最大位数，默认为 20, 也就是 i64 的最大位数
+    pub fn with_max_int_digits(mut self, max_int_digits: usize) -> Self {
+        self.max_int_digits = max_int_digits;
+        self
+    }
+
+    /// 设置单个 list 或 dict 中 item 的最大数量，默认不限制
+    pub fn with_max_items(mut self, max_items: usize) -> Self {
+        self.max_items = max_items;
+        self
+    }
+
+    /// 设置是否使用严格模式，参见 BdecodeNode::parse_strict()
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// 设置顶层的值之后还有数据时的处理方式，默认忽略
+    pub fn with_trailing_data(mut self, trailing_data: TrailingDataPolicy) -> Self {
+        self.trailing_data = trailing_data;
+        self
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt::Display;
+
+use super::{
+    token::{BdecodeTokenType, BdecodeTokens},
+    utils::str_token_value,
+};
+
+/// 路径中的一段：dict 的 key 或者 list 的索引
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    Key(Vec<u8>),
+    Index(usize),
+}
+
+/// 节点从 root 开始的逻辑路径，例如 `info.files[3].path[0]`, root 节点的路径为空
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BdecodePath(Vec<PathSegment>);
+
+impl BdecodePath {
+    pub fn new(segments: Vec<PathSegment>) -> Self {
+        Self(segments)
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.0
+    }
+
+    /// 是否为 root 节点的路径
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, segment: PathSegment) {
+        self.0.push(segment);
+    }
+}
+
+impl Display for BdecodePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if i == 0 => write!(f, "{}", String::from_utf8_lossy(key))?,
+                PathSegment::Key(key) => write!(f, ".{}", String::from_utf8_lossy(key))?,
+                PathSegment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 从 root 节点开始向下查找，生成 token 对应的节点的路径
+pub(crate) fn token_path(tokens: &BdecodeTokens, buffer: &[u8], token_idx: usize) -> BdecodePath {
+    let mut path = BdecodePath::default();
+    let mut current = 0;
+
+    while current != token_idx {
+        let node_type = tokens.node_type(current);
+        let mut child = current + 1;
+        let mut index = 0;
+
+        // 找到包含 token_idx 的子节点，dict 中 key 和 value 交替出现
+        loop {
+            if tokens.node_type(child) == BdecodeTokenType::End {
+                return path;
+            }
+
+            let (key, value) = match node_type {
+                BdecodeTokenType::Dict => (Some(child), child + tokens.get(child).next_item() as usize),
+                _ => (None, child),
+            };
+            let next = value + tokens.get(value).next_item() as usize;
+
+            if token_idx < next {
+                match key {
+                    Some(key) => path.push(PathSegment::Key(str_token_value(tokens, buffer, key).to_vec())),
+                    None => path.push(PathSegment::Index(index)),
+                }
+
+                // token_idx 是 dict 的 key 时，路径指向对应的 value
+                current = if key == Some(token_idx) { token_idx } else { value };
+                break;
+            }
+
+            child = next;
+            index += 1;
+        }
+    }
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BdecodeNode;
+
+    #[test]
+    fn test_token_path() {
+        let node = BdecodeNode::parse_buffer(b"d4:infod5:filesld4:pathl1:a1:beeeee".to_vec()).unwrap();
+        let BdecodeNode::Dict(dict) = &node else { panic!("not a Dict node") };
+        let tokens = &dict.tokens;
+        let buffer = node.buffer_bytes();
+
+        assert_eq!("", token_path(tokens, buffer, 0).to_string());
+        assert!(token_path(tokens, buffer, 0).is_root());
+        assert_eq!("info", token_path(tokens, buffer, 2).to_string());
+        assert_eq!("info.files", token_path(tokens, buffer, 4).to_string());
+        assert_eq!("info.files[0]", token_path(tokens, buffer, 5).to_string());
+        assert_eq!("info.files[0].path[1]", token_path(tokens, buffer, 9).to_string());
+        assert_eq!(
+            vec![
+                PathSegment::Key(b"info".to_vec()),
+                PathSegment::Key(b"files".to_vec()),
+                PathSegment::Index(0),
+                PathSegment::Key(b"path".to_vec()),
+                PathSegment::Index(1),
+            ],
+            token_path(tokens, buffer, 9).segments()
+        );
+    }
+}
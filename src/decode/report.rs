@@ -0,0 +1,216 @@
+use std::fmt::Write;
+
+use crate::BdecodeError;
+
+use super::BdecodePath;
+
+/// 摘录中每行显示的字节数
+const BYTES_PER_LINE: usize = 16;
+
+impl BdecodeError {
+    /// 为错误附加出错节点的路径，root 节点的路径不会被附加
+    pub(crate) fn with_path(self, path: BdecodePath) -> Self {
+        if path.is_root() || matches!(self, BdecodeError::WithPath { .. }) {
+            return self;
+        }
+
+        BdecodeError::WithPath { path, source: Box::new(self) }
+    }
+
+    /// 去掉路径后的错误
+    pub fn kind(&self) -> &BdecodeError {
+        match self {
+            BdecodeError::WithPath { source, .. } => source.kind(),
+            e => e,
+        }
+    }
+
+    /// 出错节点从 root 开始的路径，错误发生在 root 节点时返回 None
+    pub fn path(&self) -> Option<&BdecodePath> {
+        match self {
+            BdecodeError::WithPath { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// 错误在 buffer 中的位置
+    pub fn position(&self) -> Option<usize> {
+        use BdecodeError::*;
+
+        match self {
+            ExpectedDigit(pos) | ExpectedColon(pos, _) | UnexpectedEof(pos) | ExpectedValue(pos)
+            | NegativeZero(pos) | IntegerLeadingZero(pos) | LengthLeadingZero(pos) | UnsortedKey(pos)
+            | DuplicateKey(pos) | TrailingData(pos) => Some(*pos),
+            DepthExceeded { position, .. }
+            | TokenLimitExceeded { position, .. }
+            | StringLengthExceeded { position, .. }
+            | IntegerDigitsExceeded { position, .. }
+            | ItemCountExceeded { position, .. } => Some(*position),
+            TypeMismatch { offset, .. } | IndexOutOfRange { offset, .. } | Deserialize { offset, .. } => Some(*offset),
+            WithPath { source, .. } => source.position(),
+            LimitExceeded(_) | Overflow(_) | Custom(_) | Io(_) => None,
+        }
+    }
+
+    /// 生成错误报告：错误信息，以及出错位置附近的 buffer 摘录，参见 render_excerpt()
+    pub fn render(&self, buffer: &[u8]) -> String {
+        let mut report = format!("error: {}\n", self.kind());
+        if let Some(path) = self.path() {
+            let _ = writeln!(report, "path: {path}");
+        }
+        if let Some(position) = self.position() {
+            let _ = writeln!(report, "position: {position}");
+            report.push_str(&render_excerpt(buffer, position));
+        }
+
+        report
+    }
+}
+
+/// 以 hex/ASCII 格式显示 buffer 中 offset 附近的字节，并用 '^' 标出 offset 处的字节
+///
+/// 例如：
+/// ```text
+/// 00000000  64 34 3a 69 6e 66 6f 64  35 3a 66 69 6c 65 73 6c  |d4:infod5:filesl|
+/// 00000010  69 31 65 78 65 65                                 |i1exee|
+///                    ^^                                          ^
+/// ```
+///
+/// 显示 offset 所在的行和它的前一行与后一行，offset 等于 buffer 的长度时（例如数据意外结束）标出最后一个字节之后的位置。
+pub fn render_excerpt(buffer: &[u8], offset: usize) -> String {
+    let offset = offset.min(buffer.len());
+    let line = offset / BYTES_PER_LINE;
+    let first = line.saturating_sub(1);
+    let last = (line + 1).min(buffer.len().saturating_sub(1) / BYTES_PER_LINE).max(line);
+
+    let mut excerpt = String::new();
+    for i in first..=last {
+        let start = i * BYTES_PER_LINE;
+        let bytes = &buffer[start.min(buffer.len())..(start + BYTES_PER_LINE).min(buffer.len())];
+
+        let mut hex = String::new();
+        for (j, b) in bytes.iter().enumerate() {
+            if j == BYTES_PER_LINE / 2 {
+                hex.push(' ');
+            }
+            let _ = write!(hex, "{b:02x} ");
+        }
+        let ascii: String = bytes
+            .iter()
+            .map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' })
+            .collect();
+        let _ = writeln!(excerpt, "{start:08x}  {hex:<49} |{ascii}|");
+
+        if i == line {
+            let column = offset - start;
+            let hex_column = 10 + column * 3 + usize::from(column >= BYTES_PER_LINE / 2);
+            let ascii_column = 10 + 49 + 2 + column;
+            let _ = writeln!(
+                excerpt,
+                "{}^^{}^",
+                " ".repeat(hex_column),
+                " ".repeat(ascii_column - hex_column - 2)
+            );
+        }
+    }
+
+    excerpt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BdecodeNode, ParseOptions, PathSegment};
+
+    #[test]
+    fn test_render_excerpt() {
+        let buffer = b"d4:infod5:filesli1exeee";
+        let excerpt = render_excerpt(buffer, 19);
+        let expected = [
+            "00000000  64 34 3a 69 6e 66 6f 64  35 3a 66 69 6c 65 73 6c  |d4:infod5:filesl|".to_string(),
+            "00000010  69 31 65 78 65 65 65                              |i1exeee|".to_string(),
+            format!("{}^^{}^", " ".repeat(19), " ".repeat(43)),
+            "".to_string(),
+        ];
+        assert_eq!(expected.join("\n"), excerpt);
+
+        // 数据意外结束时标出最后一个字节之后的位置
+        let excerpt = render_excerpt(b"li1e", 4);
+        let expected = [
+            "00000000  6c 69 31 65                                       |li1e|".to_string(),
+            format!("{}^^{}^", " ".repeat(22), " ".repeat(41)),
+            "".to_string(),
+        ];
+        assert_eq!(expected.join("\n"), excerpt);
+
+        // 只显示 offset 所在的行和前后各一行
+        let buffer = [b'x'; 100];
+        assert_eq!(4, render_excerpt(&buffer, 50).lines().count());
+        assert!(render_excerpt(&buffer, 50).starts_with("00000020"));
+        assert_eq!("", render_excerpt(b"", 0).lines().skip(2).collect::<String>());
+    }
+
+    #[test]
+    fn test_error_report() {
+        let buffer = b"d4:infod5:filesli1exeee".to_vec();
+        let err = BdecodeNode::parse_buffer(buffer.clone()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::ExpectedDigit(19)));
+        assert_eq!(Some(19), err.position());
+        assert_eq!(
+            &[PathSegment::Key(b"info".to_vec()), PathSegment::Key(b"files".to_vec()), PathSegment::Index(1)],
+            err.path().unwrap().segments()
+        );
+        assert_eq!(
+            "Expected digit in bencoded string at position '19' . (path 'info.files[1]')",
+            err.to_string()
+        );
+
+        let report = err.render(&buffer);
+        assert!(report.starts_with("error: Expected digit in bencoded string at position '19' .\npath: info.files[1]\nposition: 19\n"));
+        assert!(report.ends_with(&render_excerpt(&buffer, 19)));
+
+        // 正在解析 dict 的 value 时，路径指向该 value; 正在解析 key 时，路径指向 dict 本身
+        let options = ParseOptions::new().with_depth_limit(3);
+        let err = BdecodeNode::parse_with_options(b"d1:ald1:xlleee".as_slice(), &options).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::DepthExceeded { limit: 3, position: 6 }));
+        assert_eq!("a[0]", err.path().unwrap().to_string());
+        let err = BdecodeNode::parse_buffer(b"d1:ald1:xi1-eee".to_vec()).unwrap_err();
+        assert_eq!("a[0].x", err.path().unwrap().to_string(), "{err:?}");
+        let err = BdecodeNode::parse_buffer(b"d1:a1:b2:cce".to_vec()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::ExpectedValue(11)));
+        assert_eq!("cc", err.path().unwrap().to_string());
+        let err = BdecodeNode::parse_buffer(b"d1:ad1:bi1ei2eee".to_vec()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::ExpectedDigit(11)));
+        assert_eq!("a", err.path().unwrap().to_string());
+
+        // root 节点的错误没有路径
+        let err = BdecodeNode::parse_buffer(b"x".to_vec()).unwrap_err();
+        assert!(err.path().is_none());
+        assert!(matches!(err, BdecodeError::ExpectedDigit(0)));
+    }
+
+    #[test]
+    fn test_nested_error_kind() {
+        // 嵌套节点的解析错误被包装在 WithPath 中，通过 kind() 匹配原来的错误
+        let err = BdecodeNode::parse_buffer(b"d1:ali1exee".to_vec()).unwrap_err();
+        assert!(matches!(err, BdecodeError::WithPath { .. }));
+        assert!(!matches!(err, BdecodeError::ExpectedDigit(_)));
+        assert!(matches!(err.kind(), BdecodeError::ExpectedDigit(8)));
+
+        // 访问嵌套节点时的错误也是一样
+        let root = BdecodeNode::parse_buffer(b"d1:ald1:bi1eeee".to_vec()).unwrap();
+        let item = root.dict_find(b"a").unwrap().list_item(0);
+        let err = item.try_as_int().unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::TypeMismatch { expected: "int", found: "dict", offset: 5 }));
+        assert_eq!("a[0]", err.path().unwrap().to_string());
+        let err = item.try_dict_find_as_str(b"b").unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::TypeMismatch { expected: "str", found: "int", .. }));
+        assert_eq!("a[0].b", err.path().unwrap().to_string());
+        let err = root.dict_find(b"a").unwrap().try_list_item(1).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::IndexOutOfRange { index: 1, len: 1, .. }));
+
+        // 没有路径的错误，kind() 返回它本身
+        let err = BdecodeNode::parse_buffer(b"x".to_vec()).unwrap_err();
+        assert!(matches!(err.kind(), BdecodeError::ExpectedDigit(0)));
+    }
+}
@@ -5,6 +5,7 @@ use bitfields::bitfield;
 #[bitfield(u32)]
 #[derive(Clone, Copy)]
 pub(crate) struct StackFrame {
+    /// token 的索引，解析时 token 的数量不超过 limits::MAX_TOKENS, 所以 31 位足够
     #[bits(31)]
     token: u32,
 
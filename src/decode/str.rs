@@ -1,4 +1,4 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, ops::Range};
 
 use crate::decode::utils::escape_string;
 
@@ -6,20 +6,24 @@ use super::{token::BdecodeTokenType, IBdecodeNode, Style};
 
 crate::primitive_bdecode_node!(Str);
 
-impl Str {
+impl<B: AsRef<[u8]>> Str<B> {
     /// 获取当前节点的字符串值
-    pub fn value(&self) -> Cow<[u8]> {
+    pub fn value(&self) -> Cow<'_, [u8]> {
+        let buf = &self.bytes()[self.value_range()];
+
+        Cow::Borrowed(buf)
+    }
+
+    /// 获取字符串值（不包括 header）在 buffer 中的范围
+    pub(crate) fn value_range(&self) -> Range<usize> {
         assert!(self.token_type() == BdecodeTokenType::Str);
 
-        let token = &self.tokens[self.token_index as usize];
+        let token = self.tokens.get(self.token_index as usize);
         let start = token.offset() as usize;
         let header_size = token.header_size() as usize + 1;
-        let end = self.tokens[(self.token_index + 1) as usize].offset() as usize;
-
-        let buf = &self.buffer[start + header_size..end];
-        let rst = Cow::Borrowed(buf);
+        let end = self.tokens.offset((self.token_index + 1) as usize);
 
-        rst
+        start + header_size..end
     }
 
     pub fn to_json_with_style(&self, _style: Style) -> String {
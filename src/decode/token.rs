@@ -1,5 +1,12 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock, PoisonError, RwLock},
+};
+
 use bitfields::bitfield;
 
+use super::commons::limits;
+
 /// token 类型
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -37,6 +44,7 @@ impl BdecodeTokenType {
 /// Bdecode 分词
 /// 用来结构化描述 buffer 中 bencoding 编码的字符串
 #[bitfield(u64)]
+#[derive(Clone, Copy)]
 pub struct BdecodeToken {
     /// 当前节点在 bdecoded buffer 中对应的偏移位置
     #[bits(29)]
@@ -54,6 +62,9 @@ pub struct BdecodeToken {
     /// 
     /// 例如：
     /// "10:abcdefghij" 中的 header 值是 '10', 所以 header_size 为 2
+    ///
+    /// dict 的 token 没有 header, 用该字段标记 dict 的 key 是否严格按升序排列: 1 为有序，0 为无序,
+    /// 参见 BdecodeTokens::keys_sorted()
     #[bits(3)]
     header_size: u8,
 }
@@ -92,6 +103,329 @@ impl BdecodeToken {
     }
 }
 
+/// 宽 Bdecode 分词
+///
+/// BdecodeToken 中 offset 和 next_item 只有 29 位，header_size 只有 3 位，buffer 超过 512 MiB 或者字符串长度超过 7 位数字时无法保存，
+/// 此时改用 16 字节的 WideBdecodeToken.
+#[bitfield(u128)]
+#[derive(Clone, Copy)]
+pub struct WideBdecodeToken {
+    /// 当前节点在 bdecoded buffer 中对应的偏移位置
+    #[bits(64)]
+    offset: u64,
+
+    /// 当前节点类型
+    #[bits(3)]
+    node_type: BdecodeTokenType,
+
+    /// 下一个节点在 tokens vector 中相对于当前节点的偏移索引值
+    #[bits(32)]
+    next_item: u32,
+
+    /// 字符串在 bdecoded buffer 中, ':' 前的代表整数的字符串长度值
+    ///
+    /// 与 BdecodeToken 相同，dict 的 token 用该字段标记 key 是否严格按升序排列: 1 为有序，0 为无序
+    #[bits(5)]
+    header_size: u8,
+
+    #[bits(24)]
+    _padding: u32,
+}
+
+impl WideBdecodeToken {
+    pub fn new_all(offset: u64, node_type: BdecodeTokenType, next_item: u32, head_size: u8) -> Self {
+        WideBdecodeTokenBuilder::new()
+            .with_offset(offset)
+            .with_node_type(node_type)
+            .with_next_item(next_item)
+            .with_header_size(head_size)
+            .build()
+    }
+
+    pub fn new_dict(offset: u64, next_item: u32) -> Self {
+        Self::new_all(offset, BdecodeTokenType::Dict, next_item, 0)
+    }
+
+    pub fn new_list(offset: u64, next_item: u32) -> Self {
+        Self::new_all(offset, BdecodeTokenType::List, next_item, 0)
+    }
+
+    pub fn new_int(offset: u64) -> Self {
+        Self::new_all(offset, BdecodeTokenType::Int, 1, 0)
+    }
+
+    pub fn new_end(offset: u64) -> Self {
+        Self::new_all(offset, BdecodeTokenType::End, 1, 0)
+    }
+
+    pub fn new_str(offset: u64, head_size: u8) -> Self {
+        Self::new_all(offset, BdecodeTokenType::Str, 1, head_size)
+    }
+
+    /// 转换为 8 字节的 BdecodeToken, 超出 BdecodeToken 的范围时返回 None
+    pub fn to_compact(self) -> Option<BdecodeToken> {
+        if self.offset() > limits::BUFFER_MAX_OFFSET as u64
+            || self.next_item() as usize > limits::MAX_NEXT_ITEM
+            || self.header_size() as usize > limits::MAX_HEADER_SIZE
+        {
+            return None;
+        }
+
+        Some(BdecodeToken::new_all(
+            self.offset() as u32,
+            self.node_type(),
+            self.next_item(),
+            self.header_size(),
+        ))
+    }
+}
+
+impl From<BdecodeToken> for WideBdecodeToken {
+    fn from(token: BdecodeToken) -> Self {
+        Self::new_all(
+            token.offset() as u64,
+            token.node_type(),
+            token.next_item(),
+            token.header_size(),
+        )
+    }
+}
+
+/// 解析后的 token 集合
+///
+/// 默认使用 8 字节的 BdecodeToken, 当某个 token 无法放入 BdecodeToken 时（例如 buffer 超过 512 MiB ），
+/// 整个集合会自动转换为 16 字节的 WideBdecodeToken.
+///
+/// 同时保存所有 dict 和 list 的子节点索引表，参见 ChildIndexes.
+#[derive(Debug, Default)]
+pub struct BdecodeTokens {
+    tokens: TokenVec,
+
+    /// 第一次获取子节点索引时建立，token 被修改时清空
+    children: OnceLock<ChildIndexes>,
+
+    /// Dict::find_indexed() 建立的 hash 索引，key 为 dict 的 token 索引，同一个文档的所有节点共享
+    key_indexes: RwLock<HashMap<u32, Arc<KeyIndex>>>,
+}
+
+/// dict 的 key 到 key token 索引的 hash 索引
+pub(crate) type KeyIndex = HashMap<Vec<u8>, u32>;
+
+impl Clone for BdecodeTokens {
+    fn clone(&self) -> Self {
+        Self {
+            tokens: self.tokens.clone(),
+            children: self.children.clone(),
+            key_indexes: RwLock::new(self.key_indexes.read().unwrap_or_else(PoisonError::into_inner).clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum TokenVec {
+    Compact(Vec<BdecodeToken>),
+    Wide(Vec<WideBdecodeToken>),
+}
+
+impl Default for TokenVec {
+    fn default() -> Self {
+        TokenVec::Compact(Vec::new())
+    }
+}
+
+impl From<Vec<BdecodeToken>> for BdecodeTokens {
+    fn from(tokens: Vec<BdecodeToken>) -> Self {
+        Self {
+            tokens: TokenVec::Compact(tokens),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Vec<WideBdecodeToken>> for BdecodeTokens {
+    fn from(tokens: Vec<WideBdecodeToken>) -> Self {
+        Self {
+            tokens: TokenVec::Wide(tokens),
+            ..Default::default()
+        }
+    }
+}
+
+impl BdecodeTokens {
+    pub fn len(&self) -> usize {
+        match &self.tokens {
+            TokenVec::Compact(tokens) => tokens.len(),
+            TokenVec::Wide(tokens) => tokens.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 是否使用 WideBdecodeToken
+    pub fn is_wide(&self) -> bool {
+        matches!(self.tokens, TokenVec::Wide(_))
+    }
+
+    /// 获取指定索引的 token, BdecodeToken 会被转换为 WideBdecodeToken
+    pub fn get(&self, index: usize) -> WideBdecodeToken {
+        match &self.tokens {
+            TokenVec::Compact(tokens) => tokens[index].into(),
+            TokenVec::Wide(tokens) => tokens[index],
+        }
+    }
+
+    /// 获取指定索引的 token 的类型
+    pub fn node_type(&self, index: usize) -> BdecodeTokenType {
+        match &self.tokens {
+            TokenVec::Compact(tokens) => tokens[index].node_type(),
+            TokenVec::Wide(tokens) => tokens[index].node_type(),
+        }
+    }
+
+    /// 获取指定索引的 token 在 buffer 中的偏移位置
+    pub fn offset(&self, index: usize) -> usize {
+        match &self.tokens {
+            TokenVec::Compact(tokens) => tokens[index].offset() as usize,
+            TokenVec::Wide(tokens) => tokens[index].offset() as usize,
+        }
+    }
+
+    /// 指定索引的 dict 的 key 是否严格按升序排列（没有重复的 key ），由解析过程记录
+    pub fn keys_sorted(&self, index: usize) -> bool {
+        self.get(index).header_size() != 0
+    }
+
+    /// 指定索引的 list 的 item, 或者 dict 的 key 的 token 索引，其他类型的 token 返回空
+    pub fn child_indexes(&self, index: usize) -> &[u32] {
+        let children = self.children.get_or_init(|| ChildIndexes::build(self));
+
+        &children.items[children.starts[index] as usize..children.starts[index + 1] as usize]
+    }
+
+    /// 指定索引的 dict 已经建立的 hash 索引
+    pub(crate) fn key_index(&self, index: usize) -> Option<Arc<KeyIndex>> {
+        let key_indexes = self.key_indexes.read().unwrap_or_else(PoisonError::into_inner);
+
+        key_indexes.get(&(index as u32)).cloned()
+    }
+
+    /// 获取指定索引的 dict 的 hash 索引，还没有建立时由 build 建立
+    pub(crate) fn key_index_or_build(&self, index: usize, build: impl FnOnce() -> KeyIndex) -> Arc<KeyIndex> {
+        if let Some(key_index) = self.key_index(index) {
+            return key_index;
+        }
+
+        // 在锁外建立索引，多个线程同时建立时保留先写入的
+        let key_index = Arc::new(build());
+        let mut key_indexes = self.key_indexes.write().unwrap_or_else(PoisonError::into_inner);
+
+        key_indexes.entry(index as u32).or_insert(key_index).clone()
+    }
+
+    pub(crate) fn set_keys_sorted(&mut self, index: usize, sorted: bool) {
+        self.clear_indexes();
+
+        let header_size = u8::from(sorted);
+        match &mut self.tokens {
+            TokenVec::Compact(tokens) => tokens[index].set_header_size(header_size),
+            TokenVec::Wide(tokens) => tokens[index].set_header_size(header_size),
+        }
+    }
+
+    pub(crate) fn push(&mut self, token: WideBdecodeToken) {
+        self.clear_indexes();
+
+        match &mut self.tokens {
+            TokenVec::Compact(tokens) => match token.to_compact() {
+                Some(token) => tokens.push(token),
+                None => {
+                    self.widen();
+                    self.push(token);
+                }
+            },
+            TokenVec::Wide(tokens) => tokens.push(token),
+        }
+    }
+
+    pub(crate) fn set_next_item(&mut self, index: usize, next_item: u32) {
+        self.clear_indexes();
+
+        match &mut self.tokens {
+            TokenVec::Compact(tokens) if next_item as usize <= limits::MAX_NEXT_ITEM => {
+                tokens[index].set_next_item(next_item)
+            }
+            TokenVec::Compact(_) => {
+                self.widen();
+                self.set_next_item(index, next_item);
+            }
+            TokenVec::Wide(tokens) => tokens[index].set_next_item(next_item),
+        }
+    }
+
+    /// token 被修改后，之前建立的索引都不再有效
+    fn clear_indexes(&mut self) {
+        self.children.take();
+        self.key_indexes.get_mut().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+
+    /// 转换为 WideBdecodeToken
+    fn widen(&mut self) {
+        if let TokenVec::Compact(tokens) = &self.tokens {
+            let tokens = tokens.iter().map(|token| WideBdecodeToken::from(*token)).collect();
+            self.tokens = TokenVec::Wide(tokens);
+        }
+    }
+}
+
+/// 所有 dict 和 list 的子节点索引表
+///
+/// 第 i 个 token 的子节点（ list 的 item 或 dict 的 key ）的 token 索引为 `items[starts[i]..starts[i + 1]]`,
+/// 其他类型的 token 对应的范围为空。
+///
+/// 建立时遍历一次全部 token, 之后创建 dict 或 list 节点都是 O(1), 不再为每个节点遍历子节点并分配 Vec.
+/// 代价是每个 token 额外占用 4 字节（ starts ），每个 list item 或 dict key 再占用 4 字节（ items ），
+/// 对于使用 8 字节 BdecodeToken 的数据，大约相当于 token 本身内存的 50% ~ 100%.
+#[derive(Debug, Clone, Default)]
+struct ChildIndexes {
+    starts: Vec<u32>,
+    items: Vec<u32>,
+}
+
+impl ChildIndexes {
+    fn build(tokens: &BdecodeTokens) -> Self {
+        let len = tokens.len();
+        let mut starts = Vec::with_capacity(len + 1);
+        let mut items = Vec::new();
+
+        for index in 0..len {
+            starts.push(items.len() as u32);
+
+            let is_dict = match tokens.node_type(index) {
+                BdecodeTokenType::Dict => true,
+                BdecodeTokenType::List => false,
+                _ => continue,
+            };
+
+            // dict 中 key 和 value 交替出现，只记录 key
+            let mut child = index + 1;
+            let mut count = 0;
+            while child < len && tokens.node_type(child) != BdecodeTokenType::End {
+                if !is_dict || count % 2 == 0 {
+                    items.push(child as u32);
+                }
+                count += 1;
+
+                child += tokens.get(child).next_item() as usize;
+            }
+        }
+        starts.push(items.len() as u32);
+
+        Self { starts, items }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -102,4 +436,114 @@ mod tests {
         assert_eq!(BdecodeTokenType::from_bits(0), BdecodeTokenType::Dict);
         assert_eq!(0, BdecodeTokenType::from_bits(0) as u8);
     }
+
+    #[test]
+    fn test_widen_tokens() {
+        let mut tokens = BdecodeTokens::default();
+        tokens.push(WideBdecodeToken::new_list(0, 0));
+        tokens.push(WideBdecodeToken::new_str(1, 1));
+        assert!(!tokens.is_wide());
+        assert_eq!(8, std::mem::size_of::<BdecodeToken>());
+
+        // offset 超过 29 位
+        let offset = limits::BUFFER_MAX_OFFSET as u64 + 1;
+        tokens.push(WideBdecodeToken::new_int(offset));
+        assert!(tokens.is_wide());
+        assert_eq!(16, std::mem::size_of::<WideBdecodeToken>());
+        assert_eq!(3, tokens.len());
+        assert_eq!(BdecodeTokenType::Str, tokens.node_type(1));
+        assert_eq!(1, tokens.get(1).header_size());
+        assert_eq!(offset as usize, tokens.offset(2));
+
+        // header_size 超过 3 位
+        let mut tokens = BdecodeTokens::default();
+        tokens.push(WideBdecodeToken::new_str(0, 8));
+        assert!(tokens.is_wide());
+        assert_eq!(8, tokens.get(0).header_size());
+
+        // next_item 超过 29 位
+        let mut tokens = BdecodeTokens::default();
+        tokens.push(WideBdecodeToken::new_list(0, 0));
+        tokens.set_next_item(0, limits::MAX_NEXT_ITEM as u32 + 1);
+        assert!(tokens.is_wide());
+        assert_eq!(limits::MAX_NEXT_ITEM as u32 + 1, tokens.get(0).next_item());
+    }
+
+    #[test]
+    fn test_child_indexes() {
+        // 2:v1
+        let v_1 = BdecodeToken::new_str(0, 1);
+        let e_x = BdecodeToken::new_end(1);
+        let tokens = BdecodeTokens::from(vec![v_1, e_x]);
+        assert!(tokens.child_indexes(0).is_empty());
+
+        // {"k1": "v1", "k2": [1, 2], "k3": 3}
+        // str  | pos   | seq
+        // --------------------
+        // d_1  | 0     | 0
+        // 2:k1 | 1     | 1
+        // 2:v1 | 5     | 2
+        // 2:k2 | 9     | 3
+        // l_2  | 13    | 4
+        // i1e  | 14    | 5
+        // i2e  | 17    | 6
+        // e_2  | 20    | 7
+        // 2:k3 | 21    | 8
+        // i3e  | 25    | 9
+        // e_1  | 28    | 10
+        // e_x  | 29    | 11
+        let d_1 = BdecodeToken::new_dict(0, 11);
+        let k_1 = BdecodeToken::new_str(1, 1);
+        let v_1 = BdecodeToken::new_str(5, 1);
+        let k_2 = BdecodeToken::new_str(9, 1);
+        let l_2 = BdecodeToken::new_list(13, 4);
+        let i_1 = BdecodeToken::new_int(14);
+        let i_2 = BdecodeToken::new_int(17);
+        let e_2 = BdecodeToken::new_end(20);
+        let k_3 = BdecodeToken::new_str(21, 1);
+        let i_3 = BdecodeToken::new_int(25);
+        let e_1 = BdecodeToken::new_end(28);
+        let e_x = BdecodeToken::new_end(29);
+        let tokens = BdecodeTokens::from(vec![ d_1, k_1, v_1, k_2, l_2, i_1, i_2, e_2, k_3, i_3, e_1, e_x ]);
+        assert_eq!(&[1, 3, 8], tokens.child_indexes(0));
+        assert_eq!(&[5, 6], tokens.child_indexes(4));
+        assert!(tokens.child_indexes(1).is_empty());
+        assert!(tokens.child_indexes(10).is_empty());
+
+        // [1, [2], {"k4": 4}]
+        // str  | pos   | seq
+        // --------------------
+        // l_1  | 0     | 0
+        // i1e  | 1     | 1
+        // l_2  | 4     | 2
+        // i2e  | 5     | 3
+        // e_2  | 8     | 4
+        // d_3  | 9     | 5
+        // 2:k4 | 10    | 6
+        // i4e  | 14    | 7
+        // e_3  | 17    | 8
+        // e_x  | 18    | 9
+        let l_1 = BdecodeToken::new_list(0, 9);
+        let i_1 = BdecodeToken::new_int(1);
+        let l_2 = BdecodeToken::new_list(4, 3);
+        let i_2 = BdecodeToken::new_int(5);
+        let e_2 = BdecodeToken::new_end(8);
+        let d_3 = BdecodeToken::new_dict(9, 4);
+        let k_4 = BdecodeToken::new_str(10, 1);
+        let i_4 = BdecodeToken::new_int(14);
+        let e_3 = BdecodeToken::new_end(17);
+        let e_x = BdecodeToken::new_end(18);
+        let tokens = BdecodeTokens::from(vec![l_1, i_1, l_2, i_2, e_2, d_3, k_4, i_4, e_3, e_x]);
+        assert_eq!(&[1, 2, 5], tokens.child_indexes(0));
+        assert_eq!(&[3], tokens.child_indexes(2));
+        assert_eq!(&[6], tokens.child_indexes(5));
+
+        // 修改 token 后重新建立索引表
+        let mut tokens = tokens;
+        tokens.push(WideBdecodeToken::new_list(19, 3));
+        tokens.push(WideBdecodeToken::new_int(20));
+        tokens.push(WideBdecodeToken::new_end(23));
+        assert_eq!(&[11], tokens.child_indexes(10));
+        assert_eq!(&[1, 2, 5], tokens.child_indexes(0));
+    }
 }
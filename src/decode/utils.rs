@@ -1,54 +1,37 @@
-use std::sync::Arc;
+use std::ops::Range;
 
 use crate::{BdecodeError, BdecodeResult};
 
-use super::token::{BdecodeToken, BdecodeTokenType};
+use super::token::{BdecodeTokenType, BdecodeTokens};
 
-/// 为一个 Bdecode 节点生成它的子节点的索引列表，以及长度。
-pub(crate) fn gen_item_indexes(
-    tokens: &[BdecodeToken],
-    start_token_idx: usize,
-) -> (Arc<Vec<u32>>, usize) {
+/// 获取 token 对应的节点在 buffer 中的范围，包括 dict 和 list 的结尾 'e'
+pub(crate) fn token_byte_range(tokens: &BdecodeTokens, token_idx: usize) -> Range<usize> {
     use BdecodeTokenType::*;
 
-    assert!(start_token_idx < tokens.len());
+    let token = tokens.get(token_idx);
+    let start = token.offset() as usize;
 
-    if tokens.len() < 2 {
-        return (Default::default(), 0);
-    }
+    let end = match token.node_type() {
+        // next_item 指向 end token 的下一个 token, end token 的位置就是结尾 'e' 的位置
+        Dict | List => tokens.offset(token_idx + token.next_item() as usize - 1) + 1,
+        Str | Int => tokens.offset(token_idx + 1),
+        End => start,
+    };
 
-    let mut node_indexes = vec![];
-    let mut count = 0;
-
-    let mut begin = 1 + start_token_idx;
-    match tokens[start_token_idx].node_type() {
-        Dict => {
-            while tokens[begin].node_type() != End {
-                if count % 2 == 0 {
-                    node_indexes.push(begin as u32);
-                }
-                count += 1;
-
-                begin += tokens[begin].next_item() as usize;
-            }
-            count /= 2;
-        }
-        List => {
-            while tokens[begin].node_type() != End {
-                node_indexes.push(begin as u32);
-                begin += tokens[begin].next_item() as usize;
-                count += 1;
-            }
-        }
-        _ => (),
-    }
+    start..end
+}
 
-    (Arc::new(node_indexes), count)
+/// 字符串 token 的值（不包括 header ）
+pub(crate) fn str_token_value<'a>(tokens: &BdecodeTokens, buffer: &'a [u8], token_idx: usize) -> &'a [u8] {
+    let token = tokens.get(token_idx);
+    let start = token.offset() as usize + token.header_size() as usize + 1;
+
+    &buffer[start..tokens.offset(token_idx + 1)]
 }
 
 /// 检查字符串是否为整数
 pub(crate) fn check_integer(buffer: &[u8], start: usize) -> BdecodeResult<usize> {
-    let mut start = start as usize;
+    let mut start = start;
     let orgin_start = start;
     let end = buffer.len();
 
@@ -91,6 +74,21 @@ pub(crate) fn check_integer(buffer: &[u8], start: usize) -> BdecodeResult<usize>
     Ok(start)
 }
 
+/// 检查整数是否为规范（canonical）的形式，digits 是 'i' 和 'e' 之间的字符串
+pub(crate) fn check_canonical_integer(digits: &[u8], int_start: usize) -> BdecodeResult<()> {
+    let (negative, abs_digits) = match digits.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        _ => (false, digits),
+    };
+
+    match abs_digits {
+        [] => Err(BdecodeError::ExpectedDigit(int_start + digits.len() + 1)),
+        [b'0'] if negative => Err(BdecodeError::NegativeZero(int_start)),
+        [b'0', _, ..] => Err(BdecodeError::IntegerLeadingZero(int_start)),
+        _ => Ok(()),
+    }
+}
+
 /// 解析 buffer 中的整数, 当遇到 delimiter 字符时停止解析
 pub(crate) fn parse_uint(
     buffer: &[u8],
@@ -117,7 +115,7 @@ pub(crate) fn parse_uint(
         let digit = (t - b'0') as i64;
         // 检查 val + digit 是否会溢出
         if *val > i64::MAX - digit {
-            return Err(BdecodeError::Overflow(format!("{}", *val + digit)));
+            return Err(BdecodeError::Overflow(format!("{}{digit}", *val / 10)));
         }
 
         *val += digit;
@@ -131,14 +129,15 @@ pub(crate) fn gen_blanks(span: usize) -> String {
     if span == 0 {
         "".into()
     } else {
-        " ".repeat(span).into()
+        " ".repeat(span)
     }
 }
 
 pub fn escape_char(byte: u8) -> String {
     match byte {
         b' ' => " ".into(),
-        b'"' => format!("\\x{:02x}", byte),
+        // 转义引号和反斜杠，否则二进制数据（例如 info-hash 作为 dict key）和普通字符无法区分
+        b'"' | b'\\' => format!("\\x{:02x}", byte),
         _ if byte.is_ascii_graphic() =>  format!("{}", byte as char),
         _ => format!("\\x{:02x}", byte),
     }
@@ -158,6 +157,7 @@ pub fn escape_string(bytes: &[u8]) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::decode::token::BdecodeToken;
 
     #[test]
     fn test_parse_int() {
@@ -202,73 +202,57 @@ mod tests {
         let buffer = b"i1234e";
         let err = check_integer(buffer, 6).unwrap_err();
         assert!(matches!(err, BdecodeError::UnexpectedEof(_)));
+
+        let buffer = b"i9223372036854775807e";
+        assert_eq!(20, check_integer(buffer, 1).unwrap());
+
+        let buffer = b"i-9223372036854775808e";
+        assert_eq!(21, check_integer(buffer, 1).unwrap());
+
+        // 超出 i64 范围的整数在解析时不报错，由 Int::value() 报告
+        let buffer = b"i99999999999999999999e";
+        assert_eq!(21, check_integer(buffer, 1).unwrap());
+    }
+
+    #[test]
+    fn test_escape_string() {
+        assert_eq!("a b", escape_string(b"a b"));
+        assert_eq!("\\x22\\x00\\xff", escape_string(b"\"\x00\xff"));
+        // 原始数据中的 "\\x00" 与字节 0 的输出不同
+        assert_eq!("\\x5cx00", escape_string(b"\\x00"));
     }
 
     #[test]
-    fn test_gen_item_indexes() {
-        // 2:v1
-        let v_1 = BdecodeToken::new_str(0, 1);
-        let e_x = BdecodeToken::new_end(1);
-        let tokens = vec![v_1, e_x];
-        let rst = gen_item_indexes(&tokens, 0);
-        assert_eq!(rst, (Arc::new(vec![]), 0));
-
-        // {"k1": "v1", "k2": [1, 2], "k3": 3}
+    fn test_token_byte_range() {
+        // {"k1": [1, 2], "k2": "v2"}
         // str  | pos   | seq
         // --------------------
         // d_1  | 0     | 0
         // 2:k1 | 1     | 1
-        // 2:v1 | 5     | 2
-        // 2:k2 | 9     | 3
-        // l_2  | 13    | 4
-        // i1e  | 14    | 5
-        // i2e  | 17    | 6
-        // e_2  | 20    | 7
-        // 2:k3 | 21    | 8
-        // i3e  | 25    | 9
-        // e_1  | 28    | 10
-        // e_x  | 29    | 11
-        let d_1 = BdecodeToken::new_dict(0, 11);
+        // l_2  | 5     | 2
+        // i1e  | 6     | 3
+        // i2e  | 9     | 4
+        // e_2  | 12    | 5
+        // 2:k2 | 13    | 6
+        // 2:v2 | 17    | 7
+        // e_1  | 21    | 8
+        // e_x  | 22    | 9
+        let d_1 = BdecodeToken::new_dict(0, 9);
         let k_1 = BdecodeToken::new_str(1, 1);
-        let v_1 = BdecodeToken::new_str(5, 1);
-        let k_2 = BdecodeToken::new_str(9, 1);
-        let l_2 = BdecodeToken::new_list(13, 4);
-        let i_1 = BdecodeToken::new_int(14);
-        let i_2 = BdecodeToken::new_int(17);
-        let e_2 = BdecodeToken::new_end(20);
-        let k_3 = BdecodeToken::new_str(21, 1);
-        let i_3 = BdecodeToken::new_int(25);
-        let e_1 = BdecodeToken::new_end(28);
-        let e_x = BdecodeToken::new_end(29);
-        let tokens = vec![ d_1, k_1, v_1, k_2, l_2, i_1, i_2, e_2, k_3, i_3, e_1, e_x ];
-        let rst = gen_item_indexes(&tokens, 0);
-        assert_eq!(rst, (Arc::new(vec![1, 3, 8]), 3));
-
-        // [1, [2], {"k4": 4}]
-        // str  | pos   | seq
-        // --------------------
-        // l_1  | 0     | 0
-        // i1e  | 1     | 1
-        // l_2  | 4     | 2
-        // i2e  | 5     | 3
-        // e_2  | 8     | 4
-        // d_3  | 9     | 5
-        // 2:k4 | 10    | 6
-        // i4e  | 14    | 7
-        // e_3  | 17    | 8
-        // e_x  | 18    | 9
-        let l_1 = BdecodeToken::new_list(0, 9);
-        let i_1 = BdecodeToken::new_int(1);
-        let l_2 = BdecodeToken::new_list(4, 3);
-        let i_2 = BdecodeToken::new_int(5);
-        let e_2 = BdecodeToken::new_end(8);
-        let d_3 = BdecodeToken::new_dict(9, 4);
-        let k_4 = BdecodeToken::new_str(10, 1);
-        let i_4 = BdecodeToken::new_int(14);
-        let e_3 = BdecodeToken::new_end(17);
-        let e_x = BdecodeToken::new_end(18);
-        let tokens = vec![l_1, i_1, l_2, i_2, e_2, d_3, k_4, i_4, e_3, e_x];
-        let rst = gen_item_indexes(&tokens, 0);
-        assert_eq!(rst, (Arc::new(vec![1, 2, 5]), 3));
+        let l_2 = BdecodeToken::new_list(5, 4);
+        let i_1 = BdecodeToken::new_int(6);
+        let i_2 = BdecodeToken::new_int(9);
+        let e_2 = BdecodeToken::new_end(12);
+        let k_2 = BdecodeToken::new_str(13, 1);
+        let v_2 = BdecodeToken::new_str(17, 1);
+        let e_1 = BdecodeToken::new_end(21);
+        let e_x = BdecodeToken::new_end(22);
+        let tokens = BdecodeTokens::from(vec![d_1, k_1, l_2, i_1, i_2, e_2, k_2, v_2, e_1, e_x]);
+
+        assert_eq!(0..22, token_byte_range(&tokens, 0));
+        assert_eq!(1..5, token_byte_range(&tokens, 1));
+        assert_eq!(5..13, token_byte_range(&tokens, 2));
+        assert_eq!(9..12, token_byte_range(&tokens, 4));
+        assert_eq!(17..21, token_byte_range(&tokens, 7));
     }
 }
@@ -0,0 +1,206 @@
+use std::io::Write;
+
+use crate::{BencodeError, BencodeResult};
+
+/// 正在写入的 list 或 dict 对应的帧
+#[derive(Debug, Clone, Copy)]
+enum Frame {
+    List,
+    /// expect_key 为 true 时，下一个写入的值必须是 dict 的 key
+    Dict { expect_key: bool },
+}
+
+/// bencoding 编码器
+///
+/// 以 begin_dict / begin_list / int / bytes / end 的方式依次写入值，
+/// 编码结果会直接写入到 `Vec<u8>` 或任意 `std::io::Write` 中。
+///
+/// 例如：
+/// ```
+/// use ez_bencoding::BencodeWriter;
+///
+/// let mut writer = BencodeWriter::new(Vec::new());
+/// writer.begin_dict().unwrap()
+///     .bytes(b"k1").unwrap().int(1).unwrap()
+///     .bytes(b"k2").unwrap().begin_list().unwrap().bytes(b"v2").unwrap().end().unwrap()
+///     .end().unwrap();
+///
+/// assert_eq!(b"d2:k1i1e2:k2l2:v2ee", writer.finish().unwrap().as_slice());
+/// ```
+pub struct BencodeWriter<W: Write> {
+    writer: W,
+
+    /// 尚未结束的 list 和 dict
+    stack: Vec<Frame>,
+
+    /// 顶层的值是否已经写完
+    completed: bool,
+}
+
+impl<W: Write> BencodeWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            stack: Vec::new(),
+            completed: false,
+        }
+    }
+
+    /// 开始写入一个 dict, 需要调用 end() 结束
+    pub fn begin_dict(&mut self) -> BencodeResult<&mut Self> {
+        self.check_value("dict")?;
+        self.writer.write_all(b"d")?;
+        self.stack.push(Frame::Dict { expect_key: true });
+
+        Ok(self)
+    }
+
+    /// 开始写入一个 list, 需要调用 end() 结束
+    pub fn begin_list(&mut self) -> BencodeResult<&mut Self> {
+        self.check_value("list")?;
+        self.writer.write_all(b"l")?;
+        self.stack.push(Frame::List);
+
+        Ok(self)
+    }
+
+    /// 写入一个整数
+    pub fn int(&mut self, val: i64) -> BencodeResult<&mut Self> {
+        self.check_value("int")?;
+        write!(self.writer, "i{val}e")?;
+        self.value_completed();
+
+        Ok(self)
+    }
+
+    /// 写入一个字节串，也可以作为 dict 的 key
+    pub fn bytes(&mut self, val: &[u8]) -> BencodeResult<&mut Self> {
+        if self.completed {
+            return Err(BencodeError::UnexpectedValue);
+        }
+
+        write!(self.writer, "{}:", val.len())?;
+        self.writer.write_all(val)?;
+
+        match self.stack.last_mut() {
+            // key 写完后，接着要写入的是 value
+            Some(Frame::Dict { expect_key }) if *expect_key => *expect_key = false,
+            _ => self.value_completed(),
+        }
+
+        Ok(self)
+    }
+
+    /// 结束当前的 list 或 dict
+    pub fn end(&mut self) -> BencodeResult<&mut Self> {
+        match self.stack.last() {
+            None => return Err(BencodeError::UnexpectedEnd),
+            Some(Frame::Dict { expect_key: false }) => return Err(BencodeError::ExpectedValue),
+            _ => (),
+        }
+
+        self.writer.write_all(b"e")?;
+        self.stack.pop();
+        self.value_completed();
+
+        Ok(self)
+    }
+
+    /// 当前尚未结束的 list 和 dict 的嵌套深度
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// 结束编码，并返回内部的 writer
+    pub fn finish(mut self) -> BencodeResult<W> {
+        if !self.stack.is_empty() {
+            return Err(BencodeError::Unclosed(self.stack.len()));
+        }
+
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    /// 检查当前位置是否可以写入一个非字节串的值
+    fn check_value(&self, kind: &'static str) -> BencodeResult<()> {
+        if self.completed {
+            return Err(BencodeError::UnexpectedValue);
+        }
+
+        if let Some(Frame::Dict { expect_key: true }) = self.stack.last() {
+            return Err(BencodeError::ExpectedKey(kind));
+        }
+
+        Ok(())
+    }
+
+    /// 一个完整的值写完后，切换父节点的状态
+    fn value_completed(&mut self) {
+        match self.stack.last_mut() {
+            Some(Frame::Dict { expect_key }) => *expect_key = true,
+            Some(Frame::List) => (),
+            None => self.completed = true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_values() {
+        let mut writer = BencodeWriter::new(Vec::new());
+        writer.int(-19).unwrap();
+        assert_eq!(b"i-19e", writer.finish().unwrap().as_slice());
+
+        let mut writer = BencodeWriter::new(Vec::new());
+        writer.bytes(b"").unwrap();
+        assert_eq!(b"0:", writer.finish().unwrap().as_slice());
+
+        // {"k1": "v1", "k2": [1, {"k3": 3}], "k4": {}}
+        let mut writer = BencodeWriter::new(Vec::new());
+        writer
+            .begin_dict().unwrap()
+            .bytes(b"k1").unwrap().bytes(b"v1").unwrap()
+            .bytes(b"k2").unwrap()
+            .begin_list().unwrap()
+            .int(1).unwrap()
+            .begin_dict().unwrap().bytes(b"k3").unwrap().int(3).unwrap().end().unwrap()
+            .end().unwrap()
+            .bytes(b"k4").unwrap().begin_dict().unwrap().end().unwrap()
+            .end().unwrap();
+        assert_eq!(0, writer.depth());
+
+        let buffer = writer.finish().unwrap();
+        assert_eq!(b"d2:k12:v12:k2li1ed2:k3i3eee2:k4dee", buffer.as_slice());
+
+        let node = crate::BdecodeNode::parse_buffer(buffer).unwrap();
+        assert_eq!(3, node.len());
+        assert_eq!(b"v1", node.dict_find_as_str(b"k1").unwrap().as_ref());
+    }
+
+    #[test]
+    fn test_write_errors() {
+        let mut writer = BencodeWriter::new(Vec::new());
+        writer.begin_dict().unwrap();
+        assert!(matches!(writer.int(1), Err(BencodeError::ExpectedKey("int"))));
+        assert!(matches!(writer.begin_list(), Err(BencodeError::ExpectedKey("list"))));
+
+        writer.bytes(b"k1").unwrap();
+        assert!(matches!(writer.end(), Err(BencodeError::ExpectedValue)));
+
+        writer.begin_list().unwrap();
+        assert_eq!(2, writer.depth());
+        assert!(matches!(writer.finish(), Err(BencodeError::Unclosed(2))));
+
+        let mut writer = BencodeWriter::new(Vec::new());
+        assert!(matches!(writer.end(), Err(BencodeError::UnexpectedEnd)));
+
+        writer.begin_list().unwrap().end().unwrap();
+        assert!(matches!(writer.int(1), Err(BencodeError::UnexpectedValue)));
+        assert!(matches!(writer.bytes(b"k1"), Err(BencodeError::UnexpectedValue)));
+        assert!(matches!(writer.end(), Err(BencodeError::UnexpectedEnd)));
+    }
+}
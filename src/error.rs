@@ -1,5 +1,21 @@
+use std::sync::Arc;
+
 use thiserror::Error;
 
+use crate::BdecodePath;
+
+/// 解析和访问 bencode 时的错误
+///
+/// 发生在嵌套节点上的错误会被包装在 WithPath 中，匹配具体的错误时应该使用 kind(),
+/// 而不是直接匹配 err 本身：
+///
+/// ```
+/// use ez_bencoding::{BdecodeError, BdecodeNode};
+///
+/// let err = BdecodeNode::parse_buffer(b"d1:ali1exee".to_vec()).unwrap_err();
+/// assert!(matches!(err.kind(), BdecodeError::ExpectedDigit(8)));
+/// assert_eq!("a[1]", err.path().unwrap().to_string());
+/// ```
 #[derive(Debug, Clone, Error)]
 pub enum BdecodeError {
     #[error("Expected digit in bencoded string at position '{0}' .")]
@@ -14,12 +30,153 @@ pub enum BdecodeError {
     #[error("Expected value (list, dict, int or string) in bencoded string at position '{0}' .")]
     ExpectedValue(usize),
 
-    #[error("bencoded recursion depth limit exceeded over '{0}' times.")]
-    DepthExceeded(usize),
+    #[error("Depth limit '{limit}' exceeded at position '{position}' .")]
+    DepthExceeded { limit: usize, position: usize },
+
+    #[error("Token limit '{limit}' exceeded at position '{position}' .")]
+    TokenLimitExceeded { limit: usize, position: usize },
+
+    #[error("String length limit '{limit}' exceeded at position '{position}' .")]
+    StringLengthExceeded { limit: usize, position: usize },
+
+    #[error("Integer digits limit '{limit}' exceeded at position '{position}' .")]
+    IntegerDigitsExceeded { limit: usize, position: usize },
+
+    #[error("Item count limit '{limit}' of list or dict exceeded at position '{position}' .")]
+    ItemCountExceeded { limit: usize, position: usize },
 
     #[error("bencoded limit exceeded over '{0}' .")]
     LimitExceeded(usize),
 
     #[error("integer overflow with string '{0}'")]
     Overflow(String),
-}
\ No newline at end of file
+
+    #[error("Negative zero integer in bencoded string at position '{0}' .")]
+    NegativeZero(usize),
+
+    #[error("Leading zero in integer in bencoded string at position '{0}' .")]
+    IntegerLeadingZero(usize),
+
+    #[error("Leading zero in string length in bencoded string at position '{0}' .")]
+    LengthLeadingZero(usize),
+
+    #[error("Unsorted dict key in bencoded string at position '{0}' .")]
+    UnsortedKey(usize),
+
+    #[error("Duplicate dict key in bencoded string at position '{0}' .")]
+    DuplicateKey(usize),
+
+    #[error("Unexpected trailing data after the top-level value at position '{0}' .")]
+    TrailingData(usize),
+
+    #[error("Type mismatch at position '{offset}', expected '{expected}' but found '{found}' .")]
+    TypeMismatch { expected: &'static str, found: &'static str, offset: usize },
+
+    #[error("Index '{index}' out of range for length '{len}' at position '{offset}' .")]
+    IndexOutOfRange { index: usize, len: usize, offset: usize },
+
+    #[error("{msg} at position '{offset}' .")]
+    Deserialize { msg: String, offset: usize },
+
+    #[error("{0}")]
+    Custom(String),
+
+    #[error("io error: {0}")]
+    Io(Arc<std::io::Error>),
+
+    /// 解析和访问嵌套节点时的错误会附加出错节点的路径，包装在该变体中,
+    /// 使用 kind() 获取并匹配原来的错误，例如 `matches!(err.kind(), BdecodeError::ExpectedDigit(_))`
+    #[error("{source} (path '{path}')")]
+    WithPath { path: BdecodePath, source: Box<BdecodeError> },
+}
+
+#[derive(Debug, Error)]
+pub enum BencodeError {
+    #[error("Expected byte string as dict key, but got '{0}' .")]
+    ExpectedKey(&'static str),
+
+    #[error("Expected value for the last dict key before end of dict .")]
+    ExpectedValue,
+
+    #[error("Unexpected end, there is no open list or dict .")]
+    UnexpectedEnd,
+
+    #[error("Unexpected value after the top-level value has been completed .")]
+    UnexpectedValue,
+
+    #[error("'{0}' list or dict are not closed .")]
+    Unclosed(usize),
+
+    #[error("Unsupported type '{0}' in bencoded value .")]
+    UnsupportedType(&'static str),
+
+    #[error("integer overflow with string '{0}'")]
+    Overflow(String),
+
+    #[error("{0}")]
+    Custom(String),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// 读取 bencode 文档（ metainfo, KRPC 消息, tracker 回复）中的字段时的错误，field 为字段在文档中的路径
+#[derive(Debug, Clone, Error)]
+pub enum FieldError {
+    #[error("Missing field '{0}' .")]
+    MissingField(String),
+
+    #[error("Invalid type of field '{field}', expected '{expected}' .")]
+    InvalidType { field: String, expected: &'static str },
+
+    #[error("Invalid value of field '{field}': {reason} .")]
+    InvalidValue { field: String, reason: String },
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum TorrentError {
+    #[error(transparent)]
+    Bdecode(#[from] BdecodeError),
+
+    #[error("Invalid torrent: {0}")]
+    Field(#[from] FieldError),
+
+    #[error("io error on '{path}': {source}")]
+    Io { path: String, source: Arc<std::io::Error> },
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum MagnetError {
+    #[error("Invalid magnet uri, expected it to start with 'magnet:?' .")]
+    InvalidScheme,
+
+    #[error("Missing exact topic ('xt') with a BitTorrent info-hash in magnet uri .")]
+    MissingInfoHash,
+
+    #[error("Invalid info-hash '{0}' in magnet uri .")]
+    InvalidInfoHash(String),
+
+    #[error("Invalid percent-encoding at position '{0}' in magnet uri .")]
+    InvalidEncoding(usize),
+
+    #[error("Invalid value of parameter '{key}' in magnet uri: {reason} .")]
+    InvalidParameter { key: String, reason: String },
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum KrpcError {
+    #[error(transparent)]
+    Bdecode(#[from] BdecodeError),
+
+    #[error("Invalid krpc message: {0}")]
+    Field(#[from] FieldError),
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum TrackerError {
+    #[error(transparent)]
+    Bdecode(#[from] BdecodeError),
+
+    #[error("Invalid tracker response: {0}")]
+    Field(#[from] FieldError),
+}
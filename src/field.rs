@@ -0,0 +1,79 @@
+//! 读取 metainfo, KRPC 消息和 tracker 回复等 bencode 文档中的字段时共用的函数
+//!
+//! field 参数是字段在文档中的路径，例如 "info.files[3].path", 用于错误信息。
+
+use crate::{BdecodeNode, Dict, FieldError, List};
+
+pub(crate) type FieldResult<T> = Result<T, FieldError>;
+
+/// 拼接字段在文档中的路径，parent 为空时就是 key
+pub(crate) fn field_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{parent}.{key}")
+    }
+}
+
+pub(crate) fn invalid_value(field: &str, reason: &str) -> FieldError {
+    FieldError::InvalidValue {
+        field: field.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+pub(crate) fn invalid_type(field: &str, expected: &'static str) -> FieldError {
+    FieldError::InvalidType {
+        field: field.to_string(),
+        expected,
+    }
+}
+
+pub(crate) fn optional<B: AsRef<[u8]>>(dict: &Dict<B>, key: &str) -> Option<BdecodeNode<B>> {
+    dict.find(key.as_bytes())
+}
+
+pub(crate) fn required<B: AsRef<[u8]>>(dict: &Dict<B>, parent: &str, key: &str) -> FieldResult<BdecodeNode<B>> {
+    dict.find(key.as_bytes())
+        .ok_or_else(|| FieldError::MissingField(field_path(parent, key)))
+}
+
+pub(crate) fn expect_dict<B: AsRef<[u8]>>(node: &BdecodeNode<B>, field: &str) -> FieldResult<Dict<B>> {
+    match node {
+        BdecodeNode::Dict(inner_node) => Ok(inner_node.clone()),
+        _ => Err(invalid_type(field, "dict")),
+    }
+}
+
+pub(crate) fn expect_list<B: AsRef<[u8]>>(node: &BdecodeNode<B>, field: &str) -> FieldResult<List<B>> {
+    match node {
+        BdecodeNode::List(inner_node) => Ok(inner_node.clone()),
+        _ => Err(invalid_type(field, "list")),
+    }
+}
+
+/// 读取整数，超出 i64 范围时返回 InvalidValue
+pub(crate) fn expect_int<B: AsRef<[u8]>>(node: &BdecodeNode<B>, field: &str) -> FieldResult<i64> {
+    match node {
+        BdecodeNode::Int(inner_node) => inner_node.value().map_err(|e| invalid_value(field, &e.to_string())),
+        _ => Err(invalid_type(field, "int")),
+    }
+}
+
+/// 读取非负的整数，例如长度和计数
+pub(crate) fn expect_length<B: AsRef<[u8]>>(node: &BdecodeNode<B>, field: &str) -> FieldResult<u64> {
+    let val = expect_int(node, field)?;
+
+    u64::try_from(val).map_err(|_| invalid_value(field, "must not be negative"))
+}
+
+pub(crate) fn expect_bytes<B: AsRef<[u8]>>(node: &BdecodeNode<B>, field: &str) -> FieldResult<Vec<u8>> {
+    match node {
+        BdecodeNode::Str(inner_node) => Ok(inner_node.value().into_owned()),
+        _ => Err(invalid_type(field, "string")),
+    }
+}
+
+pub(crate) fn expect_string<B: AsRef<[u8]>>(node: &BdecodeNode<B>, field: &str) -> FieldResult<String> {
+    String::from_utf8(expect_bytes(node, field)?).map_err(|_| invalid_value(field, "invalid utf-8"))
+}
@@ -0,0 +1,593 @@
+use std::net::SocketAddr;
+
+use crate::compact::{encode_compact_addr, parse_compact_addr, COMPACT_V4_LEN, COMPACT_V6_LEN};
+use crate::field::{expect_bytes, expect_dict, expect_int, expect_list, invalid_value, optional, required, FieldResult};
+use crate::{BdecodeNode, BencodeValue, Dict, KrpcResult};
+
+/// DHT 节点 id 以及 info-hash 的长度
+pub const ID_LEN: usize = 20;
+
+pub type NodeId = [u8; ID_LEN];
+
+/// DHT (BEP 5) 中的 KRPC 消息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// t, 由发起查询的一方生成，回复中原样返回
+    pub transaction_id: Vec<u8>,
+
+    /// v, 客户端版本
+    pub version: Option<Vec<u8>>,
+
+    /// ro, 只读节点 (BEP 43), 其他节点不应该向它发起查询
+    pub read_only: bool,
+
+    pub body: MessageBody,
+}
+
+/// y 为 q, r, e 时对应的消息内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageBody {
+    Query(Query),
+    Response(Response),
+    Error(ErrorMessage),
+}
+
+/// 查询消息，q 为方法名，a 为参数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    Ping {
+        id: NodeId,
+    },
+    FindNode {
+        id: NodeId,
+        target: NodeId,
+    },
+    GetPeers {
+        id: NodeId,
+        info_hash: NodeId,
+    },
+    AnnouncePeer {
+        id: NodeId,
+        info_hash: NodeId,
+        port: u16,
+        token: Vec<u8>,
+
+        /// 为 true 时忽略 port, 使用 UDP 包的源端口
+        implied_port: bool,
+    },
+
+    /// 不认识的方法，保留原始的参数
+    Other {
+        method: Vec<u8>,
+        args: BencodeValue,
+    },
+}
+
+/// 回复消息的 r dict, 回复中没有方法名，不同查询的回复使用不同的字段
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Response {
+    pub id: NodeId,
+
+    /// nodes, IPv4 节点
+    pub nodes: Vec<CompactNode>,
+
+    /// nodes6, IPv6 节点 (BEP 32)
+    ///
+    /// 编码时按地址族重新分配，放在 nodes 中的 IPv6 节点写入 nodes6, 反之亦然
+    pub nodes6: Vec<CompactNode>,
+
+    /// values, get_peers 回复中的 peer 地址
+    pub values: Vec<SocketAddr>,
+
+    /// get_peers 回复中的 token, 用于之后的 announce_peer
+    pub token: Option<Vec<u8>>,
+}
+
+/// DHT 节点的 id 和地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactNode {
+    pub id: NodeId,
+    pub addr: SocketAddr,
+}
+
+/// 错误消息的 e list, 第一个元素是错误码，第二个元素是错误信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorMessage {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// 201
+    Generic,
+
+    /// 202
+    Server,
+
+    /// 203, 例如包格式错误，参数无效或者 token 错误
+    Protocol,
+
+    /// 204
+    MethodUnknown,
+
+    Other(i64),
+}
+
+impl From<i64> for ErrorCode {
+    fn from(code: i64) -> Self {
+        match code {
+            201 => ErrorCode::Generic,
+            202 => ErrorCode::Server,
+            203 => ErrorCode::Protocol,
+            204 => ErrorCode::MethodUnknown,
+            _ => ErrorCode::Other(code),
+        }
+    }
+}
+
+impl From<ErrorCode> for i64 {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::Generic => 201,
+            ErrorCode::Server => 202,
+            ErrorCode::Protocol => 203,
+            ErrorCode::MethodUnknown => 204,
+            ErrorCode::Other(code) => code,
+        }
+    }
+}
+
+impl Message {
+    /// 从解析后的 root 节点中读取 KRPC 消息
+    pub fn from_node<B: AsRef<[u8]>>(root_node: &BdecodeNode<B>) -> KrpcResult<Self> {
+        let root = expect_dict(root_node, "")?;
+
+        let transaction_id = expect_bytes(&required(&root, "", "t")?, "t")?;
+        let version = optional(&root, "v").map(|node| expect_bytes(&node, "v")).transpose()?;
+        let read_only = optional(&root, "ro")
+            .map(|node| expect_int(&node, "ro"))
+            .transpose()?
+            .is_some_and(|v| v != 0);
+
+        let body = match expect_bytes(&required(&root, "", "y")?, "y")?.as_slice() {
+            b"q" => MessageBody::Query(Query::from_dict(&root)?),
+            b"r" => MessageBody::Response(Response::from_node(&required(&root, "", "r")?)?),
+            b"e" => MessageBody::Error(ErrorMessage::from_node(&required(&root, "", "e")?)?),
+            _ => return Err(invalid_value("y", "expected 'q', 'r' or 'e'").into()),
+        };
+
+        Ok(Self {
+            transaction_id,
+            version,
+            read_only,
+            body,
+        })
+    }
+
+    /// 解析 UDP 包的内容并读取 KRPC 消息
+    pub fn from_bytes(buffer: Vec<u8>) -> KrpcResult<Self> {
+        let root = BdecodeNode::parse_buffer(buffer)?;
+
+        Self::from_node(&root)
+    }
+
+    /// 编码为规范的 bencode
+    pub fn encode(&self) -> Vec<u8> {
+        let mut root = BencodeValue::new_dict();
+        root.insert("t", self.transaction_id.as_slice());
+        if let Some(version) = &self.version {
+            root.insert("v", version.as_slice());
+        }
+        if self.read_only {
+            root.insert("ro", 1);
+        }
+
+        match &self.body {
+            MessageBody::Query(query) => {
+                root.insert("y", "q");
+                root.insert("q", query.method());
+                root.insert("a", query.to_value());
+            }
+            MessageBody::Response(response) => {
+                root.insert("y", "r");
+                root.insert("r", response.to_value());
+            }
+            MessageBody::Error(error) => {
+                root.insert("y", "e");
+                root.insert(
+                    "e",
+                    vec![BencodeValue::from(i64::from(error.code)), BencodeValue::from(error.message.as_str())],
+                );
+            }
+        }
+
+        root.encode()
+    }
+}
+
+impl Query {
+    /// 查询的方法名
+    pub fn method(&self) -> &[u8] {
+        match self {
+            Query::Ping { .. } => b"ping",
+            Query::FindNode { .. } => b"find_node",
+            Query::GetPeers { .. } => b"get_peers",
+            Query::AnnouncePeer { .. } => b"announce_peer",
+            Query::Other { method, .. } => method,
+        }
+    }
+
+    /// 发起查询的节点 id, 不认识的方法中没有 id 时返回 None
+    pub fn id(&self) -> Option<NodeId> {
+        match self {
+            Query::Ping { id }
+            | Query::FindNode { id, .. }
+            | Query::GetPeers { id, .. }
+            | Query::AnnouncePeer { id, .. } => Some(*id),
+            Query::Other { args, .. } => args.get(b"id").and_then(|id| id.as_bytes()?.try_into().ok()),
+        }
+    }
+
+    fn from_dict<B: AsRef<[u8]>>(root: &Dict<B>) -> KrpcResult<Self> {
+        let method = expect_bytes(&required(root, "", "q")?, "q")?;
+        let args_node = required(root, "", "a")?;
+        let args = expect_dict(&args_node, "a")?;
+
+        let query = match method.as_slice() {
+            b"ping" => Query::Ping {
+                id: expect_id(&args, "a", "id")?,
+            },
+            b"find_node" => Query::FindNode {
+                id: expect_id(&args, "a", "id")?,
+                target: expect_id(&args, "a", "target")?,
+            },
+            b"get_peers" => Query::GetPeers {
+                id: expect_id(&args, "a", "id")?,
+                info_hash: expect_id(&args, "a", "info_hash")?,
+            },
+            b"announce_peer" => {
+                let port = expect_int(&required(&args, "a", "port")?, "a.port")?;
+                let port = u16::try_from(port).map_err(|_| invalid_value("a.port", "out of range"))?;
+                let implied_port = optional(&args, "implied_port")
+                    .map(|node| expect_int(&node, "a.implied_port"))
+                    .transpose()?
+                    .is_some_and(|v| v != 0);
+
+                Query::AnnouncePeer {
+                    id: expect_id(&args, "a", "id")?,
+                    info_hash: expect_id(&args, "a", "info_hash")?,
+                    port,
+                    token: expect_bytes(&required(&args, "a", "token")?, "a.token")?,
+                    implied_port,
+                }
+            }
+            _ => Query::Other {
+                method,
+                args: BencodeValue::try_from(&args_node)?,
+            },
+        };
+
+        Ok(query)
+    }
+
+    fn to_value(&self) -> BencodeValue {
+        let mut args = BencodeValue::new_dict();
+        match self {
+            Query::Ping { id } => {
+                args.insert("id", id);
+            }
+            Query::FindNode { id, target } => {
+                args.insert("id", id);
+                args.insert("target", target);
+            }
+            Query::GetPeers { id, info_hash } => {
+                args.insert("id", id);
+                args.insert("info_hash", info_hash);
+            }
+            Query::AnnouncePeer {
+                id,
+                info_hash,
+                port,
+                token,
+                implied_port,
+            } => {
+                args.insert("id", id);
+                args.insert("info_hash", info_hash);
+                args.insert("port", *port as i64);
+                args.insert("token", token.as_slice());
+                if *implied_port {
+                    args.insert("implied_port", 1);
+                }
+            }
+            Query::Other { args, .. } => return args.clone(),
+        }
+
+        args
+    }
+}
+
+impl Response {
+    fn from_node<B: AsRef<[u8]>>(node: &BdecodeNode<B>) -> KrpcResult<Self> {
+        let response = expect_dict(node, "r")?;
+
+        let nodes = optional(&response, "nodes")
+            .map(|node| expect_nodes(&node, "r.nodes", COMPACT_V4_LEN))
+            .transpose()?
+            .unwrap_or_default();
+        let nodes6 = optional(&response, "nodes6")
+            .map(|node| expect_nodes(&node, "r.nodes6", COMPACT_V6_LEN))
+            .transpose()?
+            .unwrap_or_default();
+
+        // values 是 compact peer 地址的 list, IPv4 为 6 字节，IPv6 为 18 字节
+        let values = match optional(&response, "values") {
+            Some(node) => {
+                let values = expect_list(&node, "r.values")?;
+                (0..values.len())
+                    .map(|i| {
+                        let field = format!("r.values[{i}]");
+                        let value = expect_bytes(&values.item(i), &field)?;
+                        parse_compact_addr(&value).ok_or_else(|| invalid_value(&field, "invalid compact peer address"))
+                    })
+                    .collect::<FieldResult<_>>()?
+            }
+            None => Vec::new(),
+        };
+
+        let token = optional(&response, "token")
+            .map(|node| expect_bytes(&node, "r.token"))
+            .transpose()?;
+
+        Ok(Self {
+            id: expect_id(&response, "r", "id")?,
+            nodes,
+            nodes6,
+            values,
+            token,
+        })
+    }
+
+    fn to_value(&self) -> BencodeValue {
+        let mut response = BencodeValue::new_dict();
+        response.insert("id", &self.id);
+        // nodes 中每个节点的长度是固定的，不同地址族的节点不能混在一起
+        let (nodes, nodes6): (Vec<_>, Vec<_>) = self.nodes.iter().chain(&self.nodes6).partition(|n| n.addr.is_ipv4());
+        if !nodes.is_empty() {
+            response.insert("nodes", encode_nodes(&nodes));
+        }
+        if !nodes6.is_empty() {
+            response.insert("nodes6", encode_nodes(&nodes6));
+        }
+        if !self.values.is_empty() {
+            let values = self
+                .values
+                .iter()
+                .map(|addr| {
+                    let mut value = Vec::with_capacity(COMPACT_V6_LEN);
+                    encode_compact_addr(addr, &mut value);
+                    BencodeValue::from(value)
+                })
+                .collect::<Vec<_>>();
+            response.insert("values", values);
+        }
+        if let Some(token) = &self.token {
+            response.insert("token", token.as_slice());
+        }
+
+        response
+    }
+}
+
+impl ErrorMessage {
+    fn from_node<B: AsRef<[u8]>>(node: &BdecodeNode<B>) -> KrpcResult<Self> {
+        let error = expect_list(node, "e")?;
+        if error.len() < 2 {
+            return Err(invalid_value("e", "expected error code and message").into());
+        }
+
+        let code = expect_int(&error.item(0), "e[0]")?;
+        let message = String::from_utf8_lossy(&expect_bytes(&error.item(1), "e[1]")?).into_owned();
+
+        Ok(Self {
+            code: code.into(),
+            message,
+        })
+    }
+}
+
+fn expect_nodes<B: AsRef<[u8]>>(node: &BdecodeNode<B>, field: &str, addr_len: usize) -> KrpcResult<Vec<CompactNode>> {
+    let nodes = expect_bytes(node, field)?;
+    let node_len = ID_LEN + addr_len;
+    if !nodes.len().is_multiple_of(node_len) {
+        return Err(invalid_value(field, &format!("length is not a multiple of {node_len}")).into());
+    }
+
+    let nodes = nodes
+        .chunks_exact(node_len)
+        .map(|node| {
+            let (id, addr) = node.split_at(ID_LEN);
+            CompactNode {
+                id: id.try_into().expect("id is 20 bytes"),
+                addr: parse_compact_addr(addr).expect("addr is 6 or 18 bytes"),
+            }
+        })
+        .collect();
+
+    Ok(nodes)
+}
+
+fn encode_nodes(nodes: &[&CompactNode]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(nodes.len() * (ID_LEN + COMPACT_V6_LEN));
+    for node in nodes {
+        buffer.extend_from_slice(&node.id);
+        encode_compact_addr(&node.addr, &mut buffer);
+    }
+
+    buffer
+}
+
+/// 读取 dict 中 20 字节的节点 id 或 info-hash
+fn expect_id<B: AsRef<[u8]>>(dict: &Dict<B>, parent: &str, key: &str) -> FieldResult<NodeId> {
+    let field = format!("{parent}.{key}");
+    let id = expect_bytes(&required(dict, parent, key)?, &field)?;
+
+    id.try_into().map_err(|_| invalid_value(&field, "length is not 20"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FieldError, KrpcError};
+
+    /// 检查解析结果并且重新编码后与原始数据一致
+    fn round_trip(buffer: &[u8]) -> Message {
+        // 直接借用 buffer 解析，不复制数据
+        let root = BdecodeNode::parse_from(buffer, None, None).unwrap();
+        let message = Message::from_node(&root).unwrap();
+        assert_eq!(buffer, message.encode().as_slice());
+
+        message
+    }
+
+    #[test]
+    fn test_queries() {
+        let message = round_trip(b"d1:ad2:id20:abcdefghij0123456789e1:q4:ping1:t2:aa1:y1:qe");
+        assert_eq!(b"aa", message.transaction_id.as_slice());
+        assert_eq!(None, message.version);
+        assert!(!message.read_only);
+        assert_eq!(MessageBody::Query(Query::Ping { id: *b"abcdefghij0123456789" }), message.body);
+
+        let message =
+            round_trip(b"d1:ad2:id20:abcdefghij01234567896:target20:mnopqrstuvwxyz123456e1:q9:find_node1:t2:aa1:y1:qe");
+        assert_eq!(
+            MessageBody::Query(Query::FindNode {
+                id: *b"abcdefghij0123456789",
+                target: *b"mnopqrstuvwxyz123456"
+            }),
+            message.body
+        );
+
+        let message = round_trip(
+            b"d1:ad2:id20:abcdefghij01234567899:info_hash20:mnopqrstuvwxyz123456e1:q9:get_peers1:t2:aa1:y1:qe",
+        );
+        assert!(matches!(message.body, MessageBody::Query(Query::GetPeers { .. })));
+
+        let message = round_trip(
+            b"d1:ad2:id20:abcdefghij012345678912:implied_porti1e9:info_hash20:mnopqrstuvwxyz1234564:porti6881e\
+            5:token8:aoeusnthe1:q13:announce_peer2:roi1e1:t2:aa1:v4:LT011:y1:qe",
+        );
+        assert_eq!(Some(b"LT01".to_vec()), message.version);
+        assert!(message.read_only);
+        assert_eq!(
+            MessageBody::Query(Query::AnnouncePeer {
+                id: *b"abcdefghij0123456789",
+                info_hash: *b"mnopqrstuvwxyz123456",
+                port: 6881,
+                token: b"aoeusnth".to_vec(),
+                implied_port: true,
+            }),
+            message.body
+        );
+
+        let message = round_trip(b"d1:ad2:id20:abcdefghij01234567896:targeti1ee1:q10:sample_inf1:t2:aa1:y1:qe");
+        let MessageBody::Query(query) = message.body else { panic!() };
+        assert_eq!(b"sample_inf", query.method());
+        assert_eq!(Some(*b"abcdefghij0123456789"), query.id());
+    }
+
+    #[test]
+    fn test_responses() {
+        let message = round_trip(b"d1:rd2:id20:mnopqrstuvwxyz123456e1:t2:aa1:y1:re");
+        assert_eq!(
+            MessageBody::Response(Response {
+                id: *b"mnopqrstuvwxyz123456",
+                ..Default::default()
+            }),
+            message.body
+        );
+
+        let message =
+            round_trip(b"d1:rd2:id20:abcdefghij01234567895:token8:aoeusnth6:valuesl6:axje.u6:idhtnmee1:t2:aa1:y1:re");
+        let MessageBody::Response(response) = message.body else { panic!() };
+        assert_eq!(Some(b"aoeusnth".to_vec()), response.token);
+        assert_eq!(
+            vec!["97.120.106.101:11893".parse::<SocketAddr>().unwrap(), "105.100.104.116:28269".parse().unwrap()],
+            response.values
+        );
+
+        let mut buffer = b"d1:rd2:id20:abcdefghij01234567895:nodes52:".to_vec();
+        buffer.extend_from_slice(b"mnopqrstuvwxyz123456\x01\x02\x03\x04\x1a\xe1");
+        buffer.extend_from_slice(b"abcdefghij0123456789\x05\x06\x07\x08\x00\x50");
+        buffer.extend_from_slice(b"e1:t2:aa1:y1:re");
+        let message = round_trip(&buffer);
+        let MessageBody::Response(response) = message.body else { panic!() };
+        assert_eq!(
+            vec![
+                CompactNode { id: *b"mnopqrstuvwxyz123456", addr: "1.2.3.4:6881".parse().unwrap() },
+                CompactNode { id: *b"abcdefghij0123456789", addr: "5.6.7.8:80".parse().unwrap() },
+            ],
+            response.nodes
+        );
+
+        // 放错字段的节点按地址族写入 nodes 和 nodes6
+        let v4 = CompactNode { id: [1u8; 20], addr: "1.2.3.4:6881".parse().unwrap() };
+        let v6 = CompactNode { id: [2u8; 20], addr: "[::1]:6881".parse().unwrap() };
+        let message = Message {
+            body: MessageBody::Response(Response {
+                nodes: vec![v6],
+                nodes6: vec![v4],
+                ..Default::default()
+            }),
+            ..message
+        };
+        let message = Message::from_bytes(message.encode()).unwrap();
+        let MessageBody::Response(response) = message.body else { panic!() };
+        assert_eq!(vec![v4], response.nodes);
+        assert_eq!(vec![v6], response.nodes6);
+    }
+
+    #[test]
+    fn test_error() {
+        let message = round_trip(b"d1:eli201e23:A Generic Error Ocurrede1:t2:aa1:y1:ee");
+        assert_eq!(
+            MessageBody::Error(ErrorMessage {
+                code: ErrorCode::Generic,
+                message: "A Generic Error Ocurred".into()
+            }),
+            message.body
+        );
+
+        assert_eq!(ErrorCode::MethodUnknown, ErrorCode::from(204));
+        assert_eq!(ErrorCode::Other(299), ErrorCode::from(299));
+        assert_eq!(203, i64::from(ErrorCode::Protocol));
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        let err = Message::from_bytes(b"d1:y1:qe".to_vec()).unwrap_err();
+        assert!(matches!(err, KrpcError::Field(FieldError::MissingField(field)) if field == "t"));
+
+        let err = Message::from_bytes(b"d1:t2:aa1:y1:xe".to_vec()).unwrap_err();
+        assert!(matches!(err, KrpcError::Field(FieldError::InvalidValue { field, .. }) if field == "y"));
+
+        let err = Message::from_bytes(b"d1:ad2:id3:abce1:q4:ping1:t2:aa1:y1:qe".to_vec()).unwrap_err();
+        assert!(matches!(err, KrpcError::Field(FieldError::InvalidValue { field, .. }) if field == "a.id"));
+
+        let err = Message::from_bytes(b"d1:ad2:id20:abcdefghij0123456789e1:q9:find_node1:t2:aa1:y1:qe".to_vec())
+            .unwrap_err();
+        assert!(matches!(err, KrpcError::Field(FieldError::MissingField(field)) if field == "a.target"));
+
+        let err = Message::from_bytes(b"d1:rd2:id20:abcdefghij01234567895:nodes3:abce1:t2:aa1:y1:re".to_vec())
+            .unwrap_err();
+        assert!(matches!(err, KrpcError::Field(FieldError::InvalidValue { field, .. }) if field == "r.nodes"));
+
+        let err = Message::from_bytes(b"d1:rde1:t2:aa1:y1:re".to_vec()).unwrap_err();
+        assert!(matches!(err, KrpcError::Field(FieldError::MissingField(field)) if field == "r.id"));
+
+        let err = Message::from_bytes(b"d1:eli201ee1:t2:aa1:y1:ee".to_vec()).unwrap_err();
+        assert!(matches!(err, KrpcError::Field(FieldError::InvalidValue { field, .. }) if field == "e"));
+
+        assert!(matches!(Message::from_bytes(b"d1:t".to_vec()), Err(KrpcError::Bdecode(_))));
+    }
+}
@@ -1,11 +1,36 @@
 #![feature(string_from_utf8_lossy_owned)]
-#![feature(ptr_as_ref_unchecked)]
-#![feature(str_from_raw_parts)]
 
 mod error;
 mod decode;
+mod encode;
+mod value;
+mod compact;
+mod field;
+pub mod torrent;
+pub mod magnet;
+pub mod krpc;
+pub mod tracker;
+#[cfg(feature = "serde")]
+mod de;
+#[cfg(feature = "serde")]
+mod ser;
+#[cfg(test)]
+mod test_utils;
 
 pub use error::*;
 pub use decode::*;
+pub use encode::*;
+pub use value::*;
+#[cfg(feature = "serde")]
+pub use de::{from_bytes, from_node, Deserializer};
+#[cfg(feature = "serde")]
+pub use ser::{to_value, to_vec, to_writer, Serializer};
+#[cfg(feature = "mmap")]
+pub use memmap2::Mmap;
 
 type BdecodeResult<T> = std::result::Result<T, BdecodeError>;
+type BencodeResult<T> = std::result::Result<T, BencodeError>;
+type TorrentResult<T> = std::result::Result<T, TorrentError>;
+type MagnetResult<T> = std::result::Result<T, MagnetError>;
+type KrpcResult<T> = std::result::Result<T, KrpcError>;
+type TrackerResult<T> = std::result::Result<T, TrackerError>;
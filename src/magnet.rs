@@ -0,0 +1,398 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::torrent::{to_hex, Metainfo};
+use crate::{MagnetError, MagnetResult};
+
+const SCHEME: &str = "magnet:?";
+
+/// v2 info-hash 的 multihash 前缀：0x12 表示 SHA-256, 0x20 表示长度为 32 字节
+const BTMH_PREFIX: &str = "1220";
+
+/// magnet 链接 (BEP 9), 支持 v2 的 urn:btmh (BEP 52) 以及文件选择 so (BEP 53)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Magnet {
+    /// xt=urn:btih, v1 的 info-hash
+    pub info_hash_v1: Option<[u8; 20]>,
+
+    /// xt=urn:btmh, v2 的 info-hash
+    pub info_hash_v2: Option<[u8; 32]>,
+
+    /// dn, 显示名称
+    pub display_name: Option<String>,
+
+    /// tr, tracker 地址，按 tier 的顺序展开并去重
+    pub trackers: Vec<String>,
+
+    /// ws, web seed 地址
+    pub web_seeds: Vec<String>,
+
+    /// xl, 所有文件的总长度
+    pub exact_length: Option<u64>,
+
+    /// so, 选择下载的文件下标范围
+    pub select_only: Vec<RangeInclusive<usize>>,
+}
+
+impl Magnet {
+    /// 根据 metainfo 生成 magnet 链接
+    pub fn from_metainfo(metainfo: &Metainfo) -> Self {
+        let info = &metainfo.info;
+
+        let mut magnet = Self {
+            info_hash_v1: info.is_v1().then_some(metainfo.info_hashes.v1),
+            info_hash_v2: info.is_v2().then_some(metainfo.info_hashes.v2),
+            display_name: Some(info.name.clone()),
+            exact_length: Some(info.total_length()),
+            ..Default::default()
+        };
+
+        let tiers = metainfo.announce_list.iter().flatten();
+        for tracker in metainfo.announce.iter().chain(tiers) {
+            push_unique(&mut magnet.trackers, tracker.clone());
+        }
+        for url in &metainfo.url_list {
+            push_unique(&mut magnet.web_seeds, url.clone());
+        }
+
+        magnet
+    }
+
+    /// 解析 magnet 链接
+    pub fn parse(uri: &str) -> MagnetResult<Self> {
+        let query = uri.strip_prefix(SCHEME).ok_or(MagnetError::InvalidScheme)?;
+
+        let mut magnet = Self::default();
+        let mut pos = SCHEME.len();
+        for param in query.split('&') {
+            let start = pos;
+            pos += param.len() + 1;
+
+            if param.is_empty() {
+                continue;
+            }
+
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let value = percent_decode(value, start + key.len() + 1)?;
+
+            // 同一参数出现多次时，可以写成 tr.1, tr.2 的形式
+            let key = match key.split_once('.') {
+                Some((name, index)) if !index.is_empty() && index.bytes().all(|c| c.is_ascii_digit()) => name,
+                _ => key,
+            };
+
+            match key {
+                "xt" => magnet.parse_exact_topic(&value)?,
+                "dn" => {
+                    magnet.display_name.get_or_insert(value);
+                }
+                "tr" => push_unique(&mut magnet.trackers, value),
+                "ws" => push_unique(&mut magnet.web_seeds, value),
+                "xl" => {
+                    let length = value.parse().map_err(|_| invalid_parameter("xl", "expected a non-negative integer"))?;
+                    magnet.exact_length = Some(length);
+                }
+                "so" => magnet.select_only = parse_select_only(&value)?,
+                // 忽略不认识的参数
+                _ => {}
+            }
+        }
+
+        if magnet.info_hash_v1.is_none() && magnet.info_hash_v2.is_none() {
+            return Err(MagnetError::MissingInfoHash);
+        }
+
+        Ok(magnet)
+    }
+
+    /// 用于 tracker 和 DHT 的 20 字节 info-hash, 没有 v1 info-hash 时使用截断的 v2 info-hash,
+    /// 两者都没有时（例如 Magnet::default() ）返回 None
+    pub fn info_hash(&self) -> Option<[u8; 20]> {
+        match (self.info_hash_v1, self.info_hash_v2) {
+            (Some(v1), _) => Some(v1),
+            (None, Some(v2)) => v2[..20].try_into().ok(),
+            (None, None) => None,
+        }
+    }
+
+    fn parse_exact_topic(&mut self, value: &str) -> MagnetResult<()> {
+        let invalid = || MagnetError::InvalidInfoHash(value.to_string());
+
+        // urn 的前缀不区分大小写
+        let (urn, hash) = match value.get(..9) {
+            Some(urn) => (urn.to_ascii_lowercase(), &value[9..]),
+            None => return Ok(()),
+        };
+
+        match urn.as_str() {
+            "urn:btih:" => {
+                // v1 的 info-hash 可以是 40 位十六进制，也可以是 32 位 base32
+                let hash = match hash.len() {
+                    40 => from_hex(hash),
+                    32 => from_base32(hash),
+                    _ => None,
+                };
+                let hash = hash.and_then(|h| h.try_into().ok()).ok_or_else(invalid)?;
+                self.info_hash_v1.get_or_insert(hash);
+            }
+            "urn:btmh:" => {
+                let hash = hash
+                    .get(..BTMH_PREFIX.len())
+                    .filter(|prefix| *prefix == BTMH_PREFIX)
+                    .and_then(|_| from_hex(&hash[BTMH_PREFIX.len()..]))
+                    .and_then(|h| h.try_into().ok())
+                    .ok_or_else(invalid)?;
+                self.info_hash_v2.get_or_insert(hash);
+            }
+            // 忽略其他协议的 urn
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Magnet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut params = Vec::new();
+
+        if let Some(v1) = &self.info_hash_v1 {
+            params.push(format!("xt=urn:btih:{}", to_hex(v1)));
+        }
+        if let Some(v2) = &self.info_hash_v2 {
+            params.push(format!("xt=urn:btmh:{BTMH_PREFIX}{}", to_hex(v2)));
+        }
+        if let Some(name) = &self.display_name {
+            params.push(format!("dn={}", percent_encode(name)));
+        }
+        if let Some(length) = self.exact_length {
+            params.push(format!("xl={length}"));
+        }
+        for tracker in &self.trackers {
+            params.push(format!("tr={}", percent_encode(tracker)));
+        }
+        for url in &self.web_seeds {
+            params.push(format!("ws={}", percent_encode(url)));
+        }
+        if !self.select_only.is_empty() {
+            let ranges = self
+                .select_only
+                .iter()
+                .map(|r| match r.start() == r.end() {
+                    true => r.start().to_string(),
+                    false => format!("{}-{}", r.start(), r.end()),
+                })
+                .collect::<Vec<_>>();
+            params.push(format!("so={}", ranges.join(",")));
+        }
+
+        write!(f, "{SCHEME}{}", params.join("&"))
+    }
+}
+
+impl FromStr for Magnet {
+    type Err = MagnetError;
+
+    fn from_str(s: &str) -> MagnetResult<Self> {
+        Self::parse(s)
+    }
+}
+
+fn push_unique(list: &mut Vec<String>, item: String) {
+    if !list.contains(&item) {
+        list.push(item);
+    }
+}
+
+fn invalid_parameter(key: &str, reason: &str) -> MagnetError {
+    MagnetError::InvalidParameter {
+        key: key.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// 解析 BEP 53 的文件下标范围，例如 "0,2,4-6"
+fn parse_select_only(value: &str) -> MagnetResult<Vec<RangeInclusive<usize>>> {
+    let invalid = || invalid_parameter("so", &format!("invalid file index range '{value}'"));
+
+    value
+        .split(',')
+        .map(|item| {
+            let (start, end) = item.split_once('-').unwrap_or((item, item));
+            let start: usize = start.parse().map_err(|_| invalid())?;
+            let end: usize = end.parse().map_err(|_| invalid())?;
+            if start > end {
+                return Err(invalid());
+            }
+
+            Ok(start..=end)
+        })
+        .collect()
+}
+
+/// 对 URI 中的参数值进行百分号编码，只保留 RFC 3986 中的非保留字符
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for &c in value.as_bytes() {
+        match c {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(c as char),
+            _ => encoded.push_str(&format!("%{c:02X}")),
+        }
+    }
+
+    encoded
+}
+
+/// 百分号解码，'+' 解码为空格，offset 是 value 在 URI 中的位置，用于错误信息
+fn percent_decode(value: &str, offset: usize) -> MagnetResult<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let byte = bytes
+                    .get(i + 1..i + 3)
+                    .and_then(hex_byte)
+                    .ok_or(MagnetError::InvalidEncoding(offset + i))?;
+                decoded.push(byte);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            c => {
+                decoded.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(decoded).map_err(|_| MagnetError::InvalidEncoding(offset))
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    hex.as_bytes().chunks_exact(2).map(hex_byte).collect()
+}
+
+/// 两个十六进制字符表示的字节
+///
+/// 不使用 u8::from_str_radix(), 它接受开头的 '+', 会把 "+F" 解码为 0x0F
+fn hex_byte(pair: &[u8]) -> Option<u8> {
+    let digit = |c: u8| (c as char).to_digit(16);
+
+    Some((digit(pair[0])? << 4 | digit(pair[1])?) as u8)
+}
+
+/// RFC 4648 base32 解码，不区分大小写，不支持填充字符
+fn from_base32(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer = 0u64;
+    let mut bits = 0;
+
+    for c in encoded.bytes() {
+        let val = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+
+        buffer = (buffer << 5) | val as u64;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1_HASH: &str = "c7ea56485743ad9ddbe26bd8e03e795549603227";
+    const V2_HASH: &str = "daff95ca9a788ed178f39cadbb04103787821dcdd1e7bc6dd220f85b6596f889";
+
+    #[test]
+    fn test_from_metainfo() {
+        let mut metainfo = Metainfo::from_bytes(include_bytes!("../res.torrent").to_vec()).unwrap();
+        metainfo.announce = Some("http://t1/announce".into());
+        metainfo.announce_list = vec![vec!["http://t1/announce".into(), "udp://t2:80".into()]];
+        metainfo.url_list = vec!["http://seed/files".into()];
+
+        let mut magnet = Magnet::from_metainfo(&metainfo);
+        assert_eq!(None, magnet.info_hash_v1);
+        assert_eq!(V2_HASH, to_hex(&magnet.info_hash_v2.unwrap()));
+        assert_eq!(&magnet.info_hash_v2.unwrap()[..20], magnet.info_hash().unwrap());
+        assert_eq!(Some(2116), magnet.exact_length);
+        assert_eq!(vec!["http://t1/announce".to_string(), "udp://t2:80".to_string()], magnet.trackers);
+
+        magnet.select_only = vec![0..=0, 2..=4];
+        let uri = magnet.to_string();
+        assert_eq!(
+            format!(
+                "magnet:?xt=urn:btmh:1220{V2_HASH}&dn={}&xl=2116\
+                &tr=http%3A%2F%2Ft1%2Fannounce&tr=udp%3A%2F%2Ft2%3A80\
+                &ws=http%3A%2F%2Fseed%2Ffiles&so=0,2-4",
+                percent_encode(&metainfo.info.name)
+            ),
+            uri
+        );
+        assert_eq!(magnet, Magnet::parse(&uri).unwrap());
+    }
+
+    #[test]
+    fn test_parse() {
+        let uri = format!("magnet:?xt=urn:btih:{V1_HASH}&dn=hello+world%21&tr.1=http%3A%2F%2Ft1&tr.2=udp://t2&xl=10&x.pe=1.2.3.4:5");
+        let magnet: Magnet = uri.parse().unwrap();
+        assert_eq!(V1_HASH, to_hex(&magnet.info_hash_v1.unwrap()));
+        assert_eq!(None, magnet.info_hash_v2);
+        assert_eq!(Some("hello world!"), magnet.display_name.as_deref());
+        assert_eq!(vec!["http://t1".to_string(), "udp://t2".to_string()], magnet.trackers);
+        assert_eq!(Some(10), magnet.exact_length);
+        assert!(magnet.select_only.is_empty());
+        assert_eq!(from_hex(V1_HASH).unwrap(), magnet.info_hash().unwrap());
+        assert_eq!(None, Magnet::default().info_hash());
+
+        // base32 编码的 btih, 以及 hybrid 的 btih + btmh
+        let uri = format!("magnet:?xt=URN:BTIH:Y7VFMSCXIOWZ3W7CNPMOAPTZKVEWAMRH&xt=urn:btmh:1220{V2_HASH}&so=1,3-5");
+        let magnet = Magnet::parse(&uri).unwrap();
+        assert_eq!(V1_HASH, to_hex(&magnet.info_hash_v1.unwrap()));
+        assert_eq!(V2_HASH, to_hex(&magnet.info_hash_v2.unwrap()));
+        assert_eq!(vec![1..=1, 3..=5], magnet.select_only);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(Magnet::parse("http://x"), Err(MagnetError::InvalidScheme)));
+        assert!(matches!(Magnet::parse("magnet:?dn=x"), Err(MagnetError::MissingInfoHash)));
+        assert!(matches!(Magnet::parse("magnet:?xt=urn:btih:1234"), Err(MagnetError::InvalidInfoHash(_))));
+        assert!(matches!(
+            Magnet::parse(&format!("magnet:?xt=urn:btmh:1114{V2_HASH}")),
+            Err(MagnetError::InvalidInfoHash(_))
+        ));
+        assert!(matches!(Magnet::parse("magnet:?dn=a%2"), Err(MagnetError::InvalidEncoding(12))));
+        assert!(matches!(Magnet::parse("magnet:?dn=%FF"), Err(MagnetError::InvalidEncoding(11))));
+        assert!(matches!(Magnet::parse("magnet:?dn=%+F"), Err(MagnetError::InvalidEncoding(11))));
+
+        // 十六进制的 info-hash 中不能包含 '+'
+        let hash = format!("+F{}", &V1_HASH[2..]);
+        for uri in [format!("magnet:?xt=urn:btih:{hash}"), format!("magnet:?xt=urn:btih:{}", hash.replace('+', "%2B"))] {
+            assert!(matches!(Magnet::parse(&uri), Err(MagnetError::InvalidInfoHash(_))), "{uri}");
+        }
+
+        let uri = format!("magnet:?xt=urn:btih:{V1_HASH}&so=3-1");
+        assert!(matches!(Magnet::parse(&uri), Err(MagnetError::InvalidParameter { key, .. }) if key == "so"));
+        let uri = format!("magnet:?xt=urn:btih:{V1_HASH}&xl=-1");
+        assert!(matches!(Magnet::parse(&uri), Err(MagnetError::InvalidParameter { key, .. }) if key == "xl"));
+    }
+}
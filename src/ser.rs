@@ -0,0 +1,498 @@
+use std::{collections::BTreeMap, fmt::Display, io::Write};
+
+use serde::ser::{
+    self, Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+    SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+};
+
+use crate::{BencodeError, BencodeResult, BencodeValue};
+
+impl ser::Error for BencodeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        BencodeError::Custom(msg.to_string())
+    }
+}
+
+/// 将 value 序列化为规范（canonical）的 bencoded 字节串
+pub fn to_vec<T: ?Sized + Serialize>(value: &T) -> BencodeResult<Vec<u8>> {
+    to_value(value)?.encode_to(Vec::new())
+}
+
+/// 将 value 序列化为规范（canonical）的 bencoded 字节串，并写入 writer
+pub fn to_writer<W: Write, T: ?Sized + Serialize>(writer: W, value: &T) -> BencodeResult<()> {
+    to_value(value)?.encode_to(writer)?;
+
+    Ok(())
+}
+
+/// 将 value 序列化为 BencodeValue
+pub fn to_value<T: ?Sized + Serialize>(value: &T) -> BencodeResult<BencodeValue> {
+    value
+        .serialize(Serializer)?
+        .ok_or(BencodeError::UnsupportedType("none"))
+}
+
+/// 序列化为 BencodeValue 的 serde 序列化器
+///
+/// - dict 的 key 和 struct 的字段名都以字节串编码，并按字节序排序；
+/// - 值为 None 的字段会被跳过，序列化结果为 None 表示没有值；
+/// - bool 编码为整数 0 或 1, 不支持浮点数和 unit;
+/// - 枚举的 unit variant 编码为字符串，其他 variant 编码为只有一个 key 的 dict 。
+pub struct Serializer;
+
+/// 将 key 序列化为字节串
+fn serialize_key<T: ?Sized + Serialize>(key: &T) -> BencodeResult<Vec<u8>> {
+    match key.serialize(Serializer)? {
+        Some(BencodeValue::Bytes(v)) => Ok(v),
+        Some(BencodeValue::Int(_)) => Err(BencodeError::ExpectedKey("int")),
+        Some(BencodeValue::List(_)) => Err(BencodeError::ExpectedKey("list")),
+        Some(BencodeValue::Dict(_)) => Err(BencodeError::ExpectedKey("dict")),
+        None => Err(BencodeError::ExpectedKey("none")),
+    }
+}
+
+/// 将 variant 包装为只有一个 key 的 dict
+fn variant_dict(variant: &'static str, value: BencodeValue) -> BencodeValue {
+    let mut dict = BTreeMap::new();
+    dict.insert(variant.as_bytes().to_vec(), value);
+
+    BencodeValue::Dict(dict)
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<BencodeValue>;
+    type Error = BencodeError;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> BencodeResult<Self::Ok> {
+        Ok(Some(BencodeValue::Int(v as i64)))
+    }
+
+    fn serialize_i8(self, v: i8) -> BencodeResult<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> BencodeResult<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> BencodeResult<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> BencodeResult<Self::Ok> {
+        Ok(Some(BencodeValue::Int(v)))
+    }
+
+    fn serialize_i128(self, v: i128) -> BencodeResult<Self::Ok> {
+        let v = i64::try_from(v).map_err(|_| BencodeError::Overflow(v.to_string()))?;
+        self.serialize_i64(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> BencodeResult<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> BencodeResult<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> BencodeResult<Self::Ok> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> BencodeResult<Self::Ok> {
+        let v = i64::try_from(v).map_err(|_| BencodeError::Overflow(v.to_string()))?;
+        self.serialize_i64(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> BencodeResult<Self::Ok> {
+        let v = i64::try_from(v).map_err(|_| BencodeError::Overflow(v.to_string()))?;
+        self.serialize_i64(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> BencodeResult<Self::Ok> {
+        Err(BencodeError::UnsupportedType("f32"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> BencodeResult<Self::Ok> {
+        Err(BencodeError::UnsupportedType("f64"))
+    }
+
+    fn serialize_char(self, v: char) -> BencodeResult<Self::Ok> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> BencodeResult<Self::Ok> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> BencodeResult<Self::Ok> {
+        Ok(Some(BencodeValue::Bytes(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> BencodeResult<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> BencodeResult<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> BencodeResult<Self::Ok> {
+        Err(BencodeError::UnsupportedType("unit"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> BencodeResult<Self::Ok> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> BencodeResult<Self::Ok> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> BencodeResult<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> BencodeResult<Self::Ok> {
+        Ok(Some(variant_dict(variant, to_value(value)?)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> BencodeResult<Self::SerializeSeq> {
+        Ok(SeqSerializer {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> BencodeResult<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> BencodeResult<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> BencodeResult<Self::SerializeTupleVariant> {
+        Ok(SeqSerializer {
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> BencodeResult<Self::SerializeMap> {
+        Ok(MapSerializer {
+            variant: None,
+            map: BTreeMap::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> BencodeResult<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> BencodeResult<Self::SerializeStructVariant> {
+        Ok(MapSerializer {
+            variant: Some(variant),
+            map: BTreeMap::new(),
+            key: None,
+        })
+    }
+}
+
+/// 用于序列化 list, tuple 以及 tuple variant
+pub struct SeqSerializer {
+    variant: Option<&'static str>,
+    items: Vec<BencodeValue>,
+}
+
+impl SeqSerializer {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> BencodeResult<()> {
+        // list 中不能跳过 None, 否则会改变其他元素的位置
+        self.items.push(to_value(value)?);
+
+        Ok(())
+    }
+
+    fn finish(self) -> BencodeResult<Option<BencodeValue>> {
+        let list = BencodeValue::List(self.items);
+
+        match self.variant {
+            Some(variant) => Ok(Some(variant_dict(variant, list))),
+            None => Ok(Some(list)),
+        }
+    }
+}
+
+impl SerializeSeq for SeqSerializer {
+    type Ok = Option<BencodeValue>;
+    type Error = BencodeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> BencodeResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> BencodeResult<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl SerializeTuple for SeqSerializer {
+    type Ok = Option<BencodeValue>;
+    type Error = BencodeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> BencodeResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> BencodeResult<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleStruct for SeqSerializer {
+    type Ok = Option<BencodeValue>;
+    type Error = BencodeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> BencodeResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> BencodeResult<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleVariant for SeqSerializer {
+    type Ok = Option<BencodeValue>;
+    type Error = BencodeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> BencodeResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> BencodeResult<Self::Ok> {
+        self.finish()
+    }
+}
+
+/// 用于序列化 map, struct 以及 struct variant
+pub struct MapSerializer {
+    variant: Option<&'static str>,
+    map: BTreeMap<Vec<u8>, BencodeValue>,
+    key: Option<Vec<u8>>,
+}
+
+impl MapSerializer {
+    fn insert<T: ?Sized + Serialize>(&mut self, key: Vec<u8>, value: &T) -> BencodeResult<()> {
+        // 跳过值为 None 的字段
+        if let Some(value) = value.serialize(Serializer)? {
+            self.map.insert(key, value);
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> BencodeResult<Option<BencodeValue>> {
+        let dict = BencodeValue::Dict(self.map);
+
+        match self.variant {
+            Some(variant) => Ok(Some(variant_dict(variant, dict))),
+            None => Ok(Some(dict)),
+        }
+    }
+}
+
+impl SerializeMap for MapSerializer {
+    type Ok = Option<BencodeValue>;
+    type Error = BencodeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> BencodeResult<()> {
+        self.key = Some(serialize_key(key)?);
+
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> BencodeResult<()> {
+        let key = self.key.take().expect("serialize_value called before serialize_key");
+
+        self.insert(key, value)
+    }
+
+    fn end(self) -> BencodeResult<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl SerializeStruct for MapSerializer {
+    type Ok = Option<BencodeValue>;
+    type Error = BencodeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> BencodeResult<()> {
+        self.insert(key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> BencodeResult<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl SerializeStructVariant for MapSerializer {
+    type Ok = Option<BencodeValue>;
+    type Error = BencodeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> BencodeResult<()> {
+        self.insert(key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> BencodeResult<Self::Ok> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Reply {
+        interval: u32,
+        #[serde(rename = "failure reason")]
+        failure_reason: Option<String>,
+        #[serde(with = "serde_bytes")]
+        peers: Vec<u8>,
+        complete: i64,
+        private: bool,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    enum Message {
+        Ping,
+        Announce(u16),
+        Peer { port: u16, ip: String },
+        Pair(i64, i64),
+    }
+
+    #[test]
+    fn test_serialize_struct() {
+        let reply = Reply {
+            interval: 1800,
+            failure_reason: None,
+            peers: vec![127, 0, 0, 1, 0x1a, 0xe1],
+            complete: -1,
+            private: true,
+        };
+
+        // 字段按 key 的字节序排序，None 字段被跳过
+        let buffer = to_vec(&reply).unwrap();
+        assert_eq!(
+            b"d8:completei-1e8:intervali1800e5:peers6:\x7f\x00\x00\x01\x1a\xe17:privatei1ee".as_slice(),
+            buffer.as_slice()
+        );
+
+        let decoded: Reply = crate::from_bytes(buffer).unwrap();
+        assert_eq!(reply, decoded);
+    }
+
+    #[test]
+    fn test_serialize_map_and_seq() {
+        let mut map = HashMap::new();
+        map.insert("zz", vec![Some(1), Some(2)]);
+        map.insert("a", vec![]);
+        assert_eq!(b"d1:ale2:zzli1ei2eee".as_slice(), to_vec(&map).unwrap().as_slice());
+
+        let list = (1, "ab", vec![b'c'], [3u8; 2]);
+        assert_eq!(b"li1e2:abli99eeli3ei3eee".as_slice(), to_vec(&list).unwrap().as_slice());
+
+        let mut buffer = Vec::new();
+        to_writer(&mut buffer, &"k").unwrap();
+        assert_eq!(b"1:k".as_slice(), buffer.as_slice());
+    }
+
+    #[test]
+    fn test_serialize_enum() {
+        let messages = vec![
+            Message::Ping,
+            Message::Announce(6881),
+            Message::Peer { port: 1, ip: "::".into() },
+            Message::Pair(1, 2),
+        ];
+        let buffer = to_vec(&messages).unwrap();
+        assert_eq!(
+            b"l4:Pingd8:Announcei6881eed4:Peerd2:ip2:::4:porti1eeed4:Pairli1ei2eeee".as_slice(),
+            buffer.as_slice()
+        );
+
+        let decoded: Vec<Message> = crate::from_bytes(buffer).unwrap();
+        assert_eq!(messages, decoded);
+    }
+
+    #[test]
+    fn test_serialize_errors() {
+        assert!(matches!(to_vec(&1.5), Err(BencodeError::UnsupportedType("f64"))));
+        assert!(matches!(to_vec(&()), Err(BencodeError::UnsupportedType("unit"))));
+        assert!(matches!(to_vec(&None::<i64>), Err(BencodeError::UnsupportedType("none"))));
+        assert!(matches!(to_vec(&vec![None::<i64>]), Err(BencodeError::UnsupportedType("none"))));
+        assert!(matches!(to_vec(&u64::MAX), Err(BencodeError::Overflow(_))));
+
+        let mut map = HashMap::new();
+        map.insert(1, 2);
+        assert!(matches!(to_vec(&map), Err(BencodeError::ExpectedKey("int"))));
+    }
+}
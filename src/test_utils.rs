@@ -0,0 +1,13 @@
+//! 测试中共用的辅助函数
+
+use std::fs;
+use std::path::PathBuf;
+
+/// 为测试创建一个空的临时目录，目录名包含测试名和进程 id, 避免并行运行的测试互相影响
+pub(crate) fn temp_dir(test_name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ez-bencoding-{test_name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
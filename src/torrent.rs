@@ -0,0 +1,698 @@
+mod builder;
+mod file_tree;
+mod info_hash;
+mod verify;
+
+use std::io;
+use std::path::{Component, Path};
+use std::sync::Arc;
+
+use crate::field::{
+    expect_bytes, expect_dict, expect_int, expect_length, expect_list, expect_string, invalid_value, optional,
+    required, FieldResult,
+};
+use crate::{BdecodeNode, Dict, FieldError, TorrentError, TorrentResult};
+
+use file_tree::{attach_piece_layers, flatten_file_tree};
+
+pub use builder::*;
+pub use file_tree::*;
+pub use info_hash::*;
+pub use verify::*;
+
+/// .torrent 文件（metainfo），支持 v1, v2 (BEP 52) 以及同时包含两者的 hybrid torrent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metainfo {
+    /// tracker 的地址
+    pub announce: Option<String>,
+
+    /// 分层的 tracker 地址列表 (BEP 12), 没有时为空
+    pub announce_list: Vec<Vec<String>>,
+
+    /// web seed 地址列表 (BEP 19), 没有时为空
+    pub url_list: Vec<String>,
+
+    pub comment: Option<String>,
+
+    pub created_by: Option<String>,
+
+    /// 创建时间，UNIX 时间戳
+    pub creation_date: Option<i64>,
+
+    pub info: Info,
+
+    /// info dict 的 v1 和 v2 info-hash
+    pub info_hashes: InfoHashes,
+}
+
+/// metainfo 中的 info dict
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Info {
+    /// 单文件模式下是文件名，多文件模式下是目录名
+    pub name: String,
+
+    /// 每个 piece 的字节数
+    pub piece_length: u64,
+
+    /// meta version, v1 torrent 中没有该字段，为 1
+    pub meta_version: i64,
+
+    /// 每个 piece 的 SHA-1 hash, v2-only torrent 中为空
+    pub pieces: Vec<[u8; 20]>,
+
+    /// v1 的文件布局，v2-only torrent 中为 None
+    pub layout: Option<FileLayout>,
+
+    /// v2 的 file tree 按顺序展开后的文件列表，v1-only torrent 中为 None
+    pub file_tree: Option<Vec<FileTreeEntry>>,
+
+    /// 是否为私有 torrent (BEP 27)
+    pub private: bool,
+
+    /// 发布来源，用于让不同站点的私有 torrent 有不同的 info-hash
+    pub source: Option<String>,
+}
+
+/// 单文件或多文件模式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileLayout {
+    Single { length: u64 },
+    Multiple { files: Vec<FileEntry> },
+}
+
+/// 多文件模式中的一个文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    pub length: u64,
+
+    /// 文件相对于 name 目录的路径
+    pub path: Vec<String>,
+
+    /// BEP 47 的文件属性，例如 "p" 表示 padding 文件，"x" 表示可执行文件
+    pub attr: Option<String>,
+}
+
+impl Metainfo {
+    /// 从解析后的 root 节点中读取 metainfo
+    pub fn from_node<B: AsRef<[u8]>>(root_node: &BdecodeNode<B>) -> TorrentResult<Self> {
+        let root = expect_dict(root_node, "")?;
+        let info = expect_dict(&required(&root, "", "info")?, "info")?;
+
+        let announce = optional(&root, "announce")
+            .map(|node| expect_string(&node, "announce"))
+            .transpose()?;
+
+        let announce_list = match optional(&root, "announce-list") {
+            Some(node) => {
+                let tiers = expect_list(&node, "announce-list")?;
+                (0..tiers.len())
+                    .map(|i| {
+                        let field = format!("announce-list[{i}]");
+                        let tier = expect_list(&tiers.item(i), &field)?;
+                        (0..tier.len())
+                            .map(|j| expect_string(&tier.item(j), &format!("{field}[{j}]")))
+                            .collect()
+                    })
+                    .collect::<FieldResult<_>>()?
+            }
+            None => Vec::new(),
+        };
+
+        // url-list 可以是单个字符串，也可以是字符串列表
+        let url_list = match optional(&root, "url-list") {
+            Some(node @ BdecodeNode::Str(_)) => vec![expect_string(&node, "url-list")?],
+            Some(node) => {
+                let urls = expect_list(&node, "url-list")?;
+                (0..urls.len())
+                    .map(|i| expect_string(&urls.item(i), &format!("url-list[{i}]")))
+                    .collect::<FieldResult<_>>()?
+            }
+            None => Vec::new(),
+        };
+
+        let comment = optional(&root, "comment")
+            .map(|node| expect_string(&node, "comment"))
+            .transpose()?;
+
+        let mut info = Info::from_dict(&info)?;
+        if let Some(file_tree) = info.file_tree.as_mut() {
+            let piece_layers = optional(&root, "piece layers");
+            attach_piece_layers(file_tree, piece_layers.as_ref(), info.piece_length)?;
+        }
+
+        let created_by = optional(&root, "created by")
+            .map(|node| expect_string(&node, "created by"))
+            .transpose()?;
+
+        let creation_date = optional(&root, "creation date")
+            .map(|node| expect_int(&node, "creation date"))
+            .transpose()?;
+
+        Ok(Self {
+            announce,
+            announce_list,
+            url_list,
+            comment,
+            created_by,
+            creation_date,
+            info,
+            info_hashes: InfoHashes::from_node(root_node)?,
+        })
+    }
+
+    /// 用于 tracker 和 DHT 的 20 字节 info-hash, v2-only torrent 使用截断的 v2 info-hash
+    pub fn info_hash(&self) -> [u8; 20] {
+        if self.info.is_v1() {
+            self.info_hashes.v1
+        } else {
+            self.info_hashes.v2_truncated()
+        }
+    }
+
+    /// 解析 .torrent 文件的内容并读取 metainfo
+    pub fn from_bytes(buffer: Vec<u8>) -> TorrentResult<Self> {
+        let root = BdecodeNode::parse_buffer(buffer)?;
+
+        Self::from_node(&root)
+    }
+}
+
+impl Info {
+    fn from_dict<B: AsRef<[u8]>>(info: &Dict<B>) -> TorrentResult<Self> {
+        let name = expect_string(&required(info, "info", "name")?, "info.name")?;
+        check_path_component(&name, "info.name")?;
+
+        // BEP 3 允许任意正数的 piece length, 只有 v2 要求是 2 的幂
+        let piece_length = expect_length(&required(info, "info", "piece length")?, "info.piece length")?;
+        if piece_length == 0 {
+            return Err(invalid_value("info.piece length", "must be greater than 0").into());
+        }
+
+        let meta_version = optional(info, "meta version")
+            .map(|node| expect_int(&node, "info.meta version"))
+            .transpose()?
+            .unwrap_or(1);
+
+        // v2 的 file tree
+        let file_tree = match meta_version {
+            1 => None,
+            2 => {
+                if !piece_length.is_power_of_two() || piece_length < MIN_PIECE_LENGTH {
+                    return Err(invalid_value("info.piece length", "must be a power of two and at least 16 KiB").into());
+                }
+
+                let tree = expect_dict(&required(info, "info", "file tree")?, "info.file tree")?;
+                Some(flatten_file_tree(&tree, "info.file tree")?)
+            }
+            _ => return Err(invalid_value("info.meta version", "unsupported meta version").into()),
+        };
+
+        // v1 的文件布局，v2-only torrent 中没有 pieces, length 和 files
+        let layout = match (optional(info, "length"), optional(info, "files")) {
+            (Some(length), None) => Some(FileLayout::Single {
+                length: expect_length(&length, "info.length")?,
+            }),
+            (None, Some(files)) => {
+                let files = expect_list(&files, "info.files")?;
+                let files = (0..files.len())
+                    .map(|i| FileEntry::from_node(&files.item(i), &format!("info.files[{i}]")))
+                    .collect::<TorrentResult<_>>()?;
+
+                Some(FileLayout::Multiple { files })
+            }
+            (Some(_), Some(_)) => {
+                return Err(invalid_value("info", "both 'length' and 'files' are present").into())
+            }
+            (None, None) if file_tree.is_some() => None,
+            (None, None) => return Err(FieldError::MissingField("info.length".into()).into()),
+        };
+
+        let pieces = match optional(info, "pieces") {
+            Some(node) => {
+                let pieces = expect_bytes(&node, "info.pieces")?;
+                if pieces.len() % 20 != 0 {
+                    return Err(invalid_value("info.pieces", "length is not a multiple of 20").into());
+                }
+
+                pieces
+                    .chunks_exact(20)
+                    .map(|hash| hash.try_into().expect("chunk size is 20"))
+                    .collect()
+            }
+            None if layout.is_none() => Vec::new(),
+            None => return Err(FieldError::MissingField("info.pieces".into()).into()),
+        };
+
+        let private = optional(info, "private")
+            .map(|node| expect_int(&node, "info.private"))
+            .transpose()?
+            .is_some_and(|v| v != 0);
+
+        let source = optional(info, "source")
+            .map(|node| expect_string(&node, "info.source"))
+            .transpose()?;
+
+        Ok(Self {
+            name,
+            piece_length,
+            meta_version,
+            pieces,
+            layout,
+            file_tree,
+            private,
+            source,
+        })
+    }
+
+    /// 是否包含 v1 的 pieces 和文件布局
+    pub fn is_v1(&self) -> bool {
+        self.layout.is_some()
+    }
+
+    /// 是否包含 v2 的 file tree
+    pub fn is_v2(&self) -> bool {
+        self.file_tree.is_some()
+    }
+
+    /// 是否同时包含 v1 和 v2 的信息
+    pub fn is_hybrid(&self) -> bool {
+        self.is_v1() && self.is_v2()
+    }
+
+    /// 所有文件的总长度
+    pub fn total_length(&self) -> u64 {
+        self.files().iter().map(|f| f.length).sum()
+    }
+
+    /// 按顺序列出所有文件，优先使用 v1 的文件布局，单文件模式下文件路径为 [name]
+    pub fn files(&self) -> Vec<FileEntry> {
+        match (&self.layout, &self.file_tree) {
+            (Some(FileLayout::Single { length }), _) => vec![FileEntry {
+                length: *length,
+                path: vec![self.name.clone()],
+                attr: None,
+            }],
+            (Some(FileLayout::Multiple { files }), _) => files.clone(),
+            (None, Some(file_tree)) => file_tree
+                .iter()
+                .map(|f| FileEntry {
+                    length: f.length,
+                    path: f.path.clone(),
+                    attr: None,
+                })
+                .collect(),
+            (None, None) => Vec::new(),
+        }
+    }
+}
+
+impl FileEntry {
+    fn from_node<B: AsRef<[u8]>>(node: &BdecodeNode<B>, field: &str) -> TorrentResult<Self> {
+        let file = expect_dict(node, field)?;
+        let length = expect_length(&required(&file, field, "length")?, &format!("{field}.length"))?;
+
+        let path_field = format!("{field}.path");
+        let path = expect_list(&required(&file, field, "path")?, &path_field)?;
+        if path.is_empty() {
+            return Err(invalid_value(&path_field, "path is empty").into());
+        }
+
+        let path = (0..path.len())
+            .map(|i| {
+                let field = format!("{path_field}[{i}]");
+                let component = expect_string(&path.item(i), &field)?;
+                check_path_component(&component, &field)?;
+
+                Ok(component)
+            })
+            .collect::<TorrentResult<_>>()?;
+
+        let attr = optional(&file, "attr")
+            .map(|node| expect_string(&node, &format!("{field}.attr")))
+            .transpose()?;
+
+        Ok(Self { length, path, attr })
+    }
+
+    /// 是否为 BEP 47 的 padding 文件
+    ///
+    /// 以 attr 中的 'p' 为准，没有 attr 时按照惯例根据路径是否以 ".pad" 目录开头判断
+    pub fn is_padding(&self) -> bool {
+        match &self.attr {
+            Some(attr) => attr.contains('p'),
+            None => self.path.first().is_some_and(|p| p == ".pad"),
+        }
+    }
+}
+
+/// name 和文件路径会被拼接到本地目录上，每一部分都必须是普通的文件名,
+/// 不能为空，不能是 "." 或 ".." , 也不能包含路径分隔符或者是绝对路径
+fn check_path_component(component: &str, field: &str) -> FieldResult<()> {
+    if component.contains(['/', '\\']) {
+        return Err(invalid_value(field, "path component contains a separator"));
+    }
+
+    let mut components = Path::new(component).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => Err(invalid_value(field, "unsafe path component")),
+    }
+}
+
+fn io_error(path: &Path, err: io::Error) -> TorrentError {
+    TorrentError::Io {
+        path: path.display().to_string(),
+        source: Arc::new(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BencodeValue;
+
+    use super::*;
+
+    fn single_file_torrent() -> BencodeValue {
+        let mut info = BencodeValue::new_dict();
+        info.insert("name", "a.txt");
+        info.insert("piece length", 16384);
+        info.insert("pieces", [1u8; 40].as_slice());
+        info.insert("length", 20000);
+
+        let mut root = BencodeValue::new_dict();
+        root.insert("announce", "http://tracker/announce");
+        root.insert(
+            "announce-list",
+            vec![
+                BencodeValue::from(vec![BencodeValue::from("http://t1"), BencodeValue::from("http://t2")]),
+                BencodeValue::from(vec![BencodeValue::from("udp://t3")]),
+            ],
+        );
+        root.insert("url-list", "http://seed/a.txt");
+        root.insert("comment", "hello");
+        root.insert("created by", "ez-bencoding");
+        root.insert("creation date", 1700000000);
+        root.insert("info", info);
+
+        root
+    }
+
+    #[test]
+    fn test_single_file() {
+        let metainfo = Metainfo::from_bytes(single_file_torrent().encode()).unwrap();
+
+        assert_eq!(Some("http://tracker/announce"), metainfo.announce.as_deref());
+        assert_eq!(
+            vec![vec!["http://t1".to_string(), "http://t2".to_string()], vec!["udp://t3".to_string()]],
+            metainfo.announce_list
+        );
+        assert_eq!(vec!["http://seed/a.txt".to_string()], metainfo.url_list);
+        assert_eq!(Some("hello"), metainfo.comment.as_deref());
+        assert_eq!(Some("ez-bencoding"), metainfo.created_by.as_deref());
+        assert_eq!(Some(1700000000), metainfo.creation_date);
+
+        let info = &metainfo.info;
+        assert_eq!("a.txt", info.name);
+        assert_eq!(16384, info.piece_length);
+        assert_eq!(vec![[1u8; 20], [1u8; 20]], info.pieces);
+        assert_eq!(Some(FileLayout::Single { length: 20000 }), info.layout);
+        assert!(info.is_v1() && !info.is_v2());
+        assert!(!info.private);
+        assert_eq!(None, info.source);
+        assert_eq!(20000, info.total_length());
+        assert_eq!(vec!["a.txt".to_string()], info.files()[0].path);
+    }
+
+    #[test]
+    fn test_multiple_files() {
+        let mut root = single_file_torrent();
+        let info = root.get_mut(b"info").unwrap();
+        info.remove(b"length");
+        info.insert("private", 1);
+        info.insert("source", "site");
+
+        let mut file_1 = BencodeValue::new_dict();
+        file_1.insert("length", 10);
+        file_1.insert("path", vec![BencodeValue::from("dir"), BencodeValue::from("b.txt")]);
+        let mut file_2 = BencodeValue::new_dict();
+        file_2.insert("length", 5);
+        file_2.insert("path", vec![BencodeValue::from("c.txt")]);
+        info.insert("files", vec![file_1, file_2]);
+        root.insert("url-list", vec![BencodeValue::from("http://s1/"), BencodeValue::from("http://s2/")]);
+
+        let metainfo = Metainfo::from_bytes(root.encode()).unwrap();
+        assert_eq!(vec!["http://s1/".to_string(), "http://s2/".to_string()], metainfo.url_list);
+
+        let info = &metainfo.info;
+        assert!(info.private);
+        assert_eq!(Some("site"), info.source.as_deref());
+        assert_eq!(15, info.total_length());
+        assert_eq!(
+            vec![
+                FileEntry { length: 10, path: vec!["dir".into(), "b.txt".into()], attr: None },
+                FileEntry { length: 5, path: vec!["c.txt".into()], attr: None },
+            ],
+            info.files()
+        );
+    }
+
+    fn v2_file(length: i64, pieces_root: Option<[u8; 32]>) -> BencodeValue {
+        let mut file = BencodeValue::new_dict();
+        file.insert("length", length);
+        if let Some(pieces_root) = pieces_root {
+            file.insert("pieces root", pieces_root.as_slice());
+        }
+
+        let mut node = BencodeValue::new_dict();
+        node.insert("", file);
+        node
+    }
+
+    fn v2_torrent() -> BencodeValue {
+        // dir/b.bin (40000 字节, 3 个 piece), a.txt (10 字节), empty (0 字节)
+        let mut dir = BencodeValue::new_dict();
+        dir.insert("b.bin", v2_file(40000, Some([2; 32])));
+
+        let mut tree = BencodeValue::new_dict();
+        tree.insert("dir", dir);
+        tree.insert("a.txt", v2_file(10, Some([1; 32])));
+        tree.insert("empty", v2_file(0, None));
+
+        let mut info = BencodeValue::new_dict();
+        info.insert("name", "v2");
+        info.insert("piece length", 16384);
+        info.insert("meta version", 2);
+        info.insert("file tree", tree);
+
+        let mut layers = BencodeValue::new_dict();
+        layers.insert([2u8; 32].as_slice(), [[3u8; 32], [4; 32], [5; 32]].concat());
+
+        let mut root = BencodeValue::new_dict();
+        root.insert("info", info);
+        root.insert("piece layers", layers);
+        root
+    }
+
+    #[test]
+    fn test_v2() {
+        let metainfo = Metainfo::from_bytes(v2_torrent().encode()).unwrap();
+        let info = &metainfo.info;
+        assert_eq!(2, info.meta_version);
+        assert!(info.is_v2() && !info.is_v1() && !info.is_hybrid());
+        assert!(info.pieces.is_empty());
+        assert_eq!(40010, info.total_length());
+
+        // 按 key 的字节序展开
+        let files = info.file_tree.as_ref().unwrap();
+        assert_eq!(
+            &vec![
+                FileTreeEntry {
+                    path: vec!["a.txt".into()],
+                    length: 10,
+                    pieces_root: Some([1; 32]),
+                    piece_layer: None,
+                },
+                FileTreeEntry {
+                    path: vec!["dir".into(), "b.bin".into()],
+                    length: 40000,
+                    pieces_root: Some([2; 32]),
+                    piece_layer: Some(vec![[3; 32], [4; 32], [5; 32]]),
+                },
+                FileTreeEntry {
+                    path: vec!["empty".into()],
+                    length: 0,
+                    pieces_root: None,
+                    piece_layer: None,
+                },
+            ],
+            files
+        );
+        assert_eq!(3, files[1].num_pieces(info.piece_length));
+    }
+
+    #[test]
+    fn test_v2_res_torrent() {
+        let metainfo = Metainfo::from_bytes(include_bytes!("../res.torrent").to_vec()).unwrap();
+        let info = &metainfo.info;
+        assert_eq!("res", info.name);
+        assert_eq!(2, info.meta_version);
+        assert_eq!(65536, info.piece_length);
+
+        let files = info.files();
+        assert_eq!(2, files.len());
+        assert_eq!(vec!["data1.txt".to_string()], files[0].path);
+        assert_eq!(10, files[0].length);
+        assert_eq!(vec!["data2.txt".to_string()], files[1].path);
+        assert_eq!(2106, files[1].length);
+    }
+
+    #[test]
+    fn test_hybrid() {
+        let mut root = v2_torrent();
+        let info = root.get_mut(b"info").unwrap();
+        info.insert("pieces", [0u8; 60].as_slice());
+        let mut file = BencodeValue::new_dict();
+        file.insert("length", 40010);
+        file.insert("path", vec![BencodeValue::from("all")]);
+        info.insert("files", vec![file]);
+
+        let metainfo = Metainfo::from_bytes(root.encode()).unwrap();
+        assert!(metainfo.info.is_hybrid());
+        assert_eq!(3, metainfo.info.pieces.len());
+        assert_eq!(vec!["all".to_string()], metainfo.info.files()[0].path);
+    }
+
+    #[test]
+    fn test_v2_errors() {
+        let mut root = v2_torrent();
+        root.remove(b"piece layers");
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::MissingField(field)) if field == "piece layers.dir/b.bin"));
+
+        let mut root = v2_torrent();
+        root.get_mut(b"piece layers").unwrap().insert([2u8; 32].as_slice(), [3u8; 64].as_slice());
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidValue { field, .. }) if field == "piece layers.dir/b.bin"));
+
+        let mut root = v2_torrent();
+        root.get_mut(b"info").unwrap().insert("piece length", 10000);
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidValue { field, .. }) if field == "info.piece length"));
+
+        let mut root = v2_torrent();
+        let tree = root.get_mut(b"info").unwrap().get_mut(b"file tree").unwrap();
+        tree.insert("a.txt", v2_file(10, None));
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::MissingField(field)) if field == "info.file tree.a.txt..pieces root"));
+
+        let mut root = v2_torrent();
+        let tree = root.get_mut(b"info").unwrap().get_mut(b"file tree").unwrap();
+        tree.get_mut(b"dir").unwrap().get_mut(b"b.bin").unwrap().insert("x", 1);
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidValue { field, .. }) if field == "info.file tree.dir.b.bin"));
+
+        let mut root = v2_torrent();
+        let tree = root.get_mut(b"info").unwrap().get_mut(b"file tree").unwrap();
+        tree.insert("..", v2_file(0, None));
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidValue { field, .. }) if field == "info.file tree..."));
+
+        let mut root = v2_torrent();
+        root.get_mut(b"info").unwrap().remove(b"file tree");
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::MissingField(field)) if field == "info.file tree"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut root = single_file_torrent();
+        root.remove(b"info");
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::MissingField(field)) if field == "info"));
+
+        let mut root = single_file_torrent();
+        root.get_mut(b"info").unwrap().insert("piece length", "16384");
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidType { field, expected: "int" }) if field == "info.piece length"));
+
+        let mut root = single_file_torrent();
+        root.get_mut(b"info").unwrap().insert("pieces", [1u8; 21].as_slice());
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidValue { field, .. }) if field == "info.pieces"));
+
+        let mut root = single_file_torrent();
+        root.get_mut(b"info").unwrap().remove(b"length");
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::MissingField(field)) if field == "info.length"));
+
+        let mut root = single_file_torrent();
+        let info = root.get_mut(b"info").unwrap();
+        info.remove(b"length");
+        let mut file = BencodeValue::new_dict();
+        file.insert("length", 1);
+        file.insert("path", vec![BencodeValue::from(1)]);
+        info.insert("files", vec![file]);
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidType { field, .. }) if field == "info.files[0].path[0]"));
+
+        // 路径不能指向 name 目录之外
+        for (i, path) in [vec!["..", "etc", "passwd"], vec!["a", "/etc"], vec!["a/b"], vec!["a", ""], vec!["."]]
+            .into_iter()
+            .enumerate()
+        {
+            let mut root = single_file_torrent();
+            let info = root.get_mut(b"info").unwrap();
+            info.remove(b"length");
+            let mut file = BencodeValue::new_dict();
+            file.insert("length", 1);
+            file.insert("path", path.into_iter().map(BencodeValue::from).collect::<Vec<_>>());
+            info.insert("files", vec![file]);
+            let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+            assert!(
+                matches!(&err, TorrentError::Field(FieldError::InvalidValue { field, .. }) if field.starts_with("info.files[0].path[")),
+                "case {i}: {err:?}"
+            );
+        }
+
+        for name in ["..", "/etc/passwd", ""] {
+            let mut root = single_file_torrent();
+            root.get_mut(b"info").unwrap().insert("name", name);
+            let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+            assert!(matches!(err, TorrentError::Field(FieldError::InvalidValue { field, .. }) if field == "info.name"));
+        }
+
+        let mut root = single_file_torrent();
+        root.get_mut(b"info").unwrap().insert("piece length", 0);
+        let err = Metainfo::from_bytes(root.encode()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidValue { field, .. }) if field == "info.piece length"));
+
+        // v1 torrent 的 piece length 可以不是 2 的幂，也可以超过 64 MiB
+        for piece_length in [10000, 128 * 1024 * 1024] {
+            let mut root = single_file_torrent();
+            root.get_mut(b"info").unwrap().insert("piece length", piece_length);
+            assert_eq!(piece_length as u64, Metainfo::from_bytes(root.encode()).unwrap().info.piece_length);
+        }
+
+        let err = Metainfo::from_bytes(b"li1ee".to_vec()).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidType { expected: "dict", .. })));
+
+        let err = Metainfo::from_bytes(b"d4:infoi1e".to_vec()).unwrap_err();
+        assert!(matches!(err, TorrentError::Bdecode(_)));
+    }
+
+    #[test]
+    fn test_padding_file() {
+        let file = |path: &str, attr: Option<&str>| FileEntry {
+            length: 1,
+            path: vec![path.into(), "0".into()],
+            attr: attr.map(Into::into),
+        };
+
+        assert!(file("a", Some("p")).is_padding());
+        assert!(file("a", Some("xp")).is_padding());
+        // 有 attr 时以 attr 为准
+        assert!(!file(".pad", Some("x")).is_padding());
+        assert!(file(".pad", None).is_padding());
+        assert!(!file("a", None).is_padding());
+    }
+}
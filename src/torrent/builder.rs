@@ -0,0 +1,434 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha1::{Digest, Sha1};
+
+use crate::field::invalid_value;
+use crate::{BencodeValue, TorrentResult};
+
+use super::io_error;
+
+/// 自动选择时 piece length 的下限
+pub const MIN_PIECE_LENGTH: u64 = 16 * 1024;
+
+/// 自动选择时 piece length 的上限
+pub const MAX_PIECE_LENGTH: u64 = 16 * 1024 * 1024;
+
+/// 创建和校验 torrent 时 piece length 的上限
+pub const PIECE_LENGTH_LIMIT: u64 = 64 * 1024 * 1024;
+
+/// 自动选择 piece length 时期望的 piece 数量
+const TARGET_PIECES: u64 = 1500;
+
+/// 由文件或目录创建 v1 torrent
+///
+/// 目录中的文件按路径排序，所以同样的内容总是生成同样的 torrent.
+#[derive(Debug, Clone)]
+pub struct TorrentBuilder {
+    path: PathBuf,
+    piece_length: Option<u64>,
+    announce: Option<String>,
+    announce_list: Vec<Vec<String>>,
+    comment: Option<String>,
+    created_by: Option<String>,
+    creation_date: Option<i64>,
+    private: bool,
+    source: Option<String>,
+}
+
+/// 待 hash 的文件
+struct SourceFile {
+    full_path: PathBuf,
+
+    /// 文件相对于根目录的路径
+    path: Vec<String>,
+
+    length: u64,
+}
+
+impl TorrentBuilder {
+    /// path 为要制作 torrent 的文件或目录，creation date 默认为当前时间
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let creation_date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .ok();
+
+        Self {
+            path: path.into(),
+            piece_length: None,
+            announce: None,
+            announce_list: Vec::new(),
+            comment: None,
+            created_by: Some(format!("ez-bencoding/{}", env!("CARGO_PKG_VERSION"))),
+            creation_date,
+            private: false,
+            source: None,
+        }
+    }
+
+    /// 指定 piece length, 必须是 16 KiB 到 64 MiB 之间的 2 的幂，不指定时根据总长度自动选择
+    pub fn with_piece_length(mut self, piece_length: u64) -> TorrentResult<Self> {
+        if !piece_length.is_power_of_two() || !(MIN_PIECE_LENGTH..=PIECE_LENGTH_LIMIT).contains(&piece_length) {
+            return Err(invalid_value(
+                "info.piece length",
+                "must be a power of two between 16 KiB and 64 MiB",
+            ).into());
+        }
+
+        self.piece_length = Some(piece_length);
+        Ok(self)
+    }
+
+    pub fn with_announce(mut self, announce: impl Into<String>) -> Self {
+        self.announce = Some(announce.into());
+        self
+    }
+
+    /// 分层的 tracker 地址列表 (BEP 12)
+    pub fn with_announce_list(mut self, announce_list: Vec<Vec<String>>) -> Self {
+        self.announce_list = announce_list;
+        self
+    }
+
+    pub fn with_comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// 为 None 时不写入 created by
+    pub fn with_created_by(mut self, created_by: Option<String>) -> Self {
+        self.created_by = created_by;
+        self
+    }
+
+    /// 为 None 时不写入 creation date
+    pub fn with_creation_date(mut self, creation_date: Option<i64>) -> Self {
+        self.creation_date = creation_date;
+        self
+    }
+
+    /// 私有 torrent (BEP 27)
+    pub fn with_private(mut self, private: bool) -> Self {
+        self.private = private;
+        self
+    }
+
+    /// 写入 info dict 的发布来源
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// 读取并 hash 所有文件，生成规范（canonical）编码的 torrent
+    pub fn build(&self) -> TorrentResult<Vec<u8>> {
+        let name = self
+            .path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| invalid_value("info.name", "path has no valid utf-8 file name"))?;
+
+        let metadata = fs::metadata(&self.path).map_err(|e| io_error(&self.path, e))?;
+        let is_dir = metadata.is_dir();
+        let files = if is_dir {
+            let mut files = Vec::new();
+            let mut ancestors = Vec::new();
+            collect_files(&self.path, &mut Vec::new(), &mut ancestors, &mut files)?;
+            if files.is_empty() {
+                return Err(invalid_value("info.files", "no files found in directory").into());
+            }
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+
+            files
+        } else {
+            vec![SourceFile {
+                full_path: self.path.clone(),
+                path: vec![name.to_string()],
+                length: metadata.len(),
+            }]
+        };
+
+        let total_length = files.iter().map(|f| f.length).sum();
+        let piece_length = self.piece_length.unwrap_or_else(|| auto_piece_length(total_length));
+
+        let mut info = BencodeValue::new_dict();
+        info.insert("name", name);
+        info.insert("piece length", piece_length as i64);
+        info.insert("pieces", hash_pieces(&files, piece_length)?);
+        if is_dir {
+            let files = files
+                .iter()
+                .map(|f| {
+                    let mut file = BencodeValue::new_dict();
+                    file.insert("length", f.length as i64);
+                    file.insert("path", f.path.iter().map(|p| p.as_str().into()).collect::<Vec<BencodeValue>>());
+                    file
+                })
+                .collect::<Vec<_>>();
+            info.insert("files", files);
+        } else {
+            info.insert("length", total_length as i64);
+        }
+        if self.private {
+            info.insert("private", 1);
+        }
+        if let Some(source) = &self.source {
+            info.insert("source", source.as_str());
+        }
+
+        let mut root = BencodeValue::new_dict();
+        if let Some(announce) = &self.announce {
+            root.insert("announce", announce.as_str());
+        }
+        if !self.announce_list.is_empty() {
+            let tiers = self
+                .announce_list
+                .iter()
+                .map(|tier| tier.iter().map(|url| url.as_str().into()).collect::<Vec<BencodeValue>>().into())
+                .collect::<Vec<BencodeValue>>();
+            root.insert("announce-list", tiers);
+        }
+        if let Some(comment) = &self.comment {
+            root.insert("comment", comment.as_str());
+        }
+        if let Some(created_by) = &self.created_by {
+            root.insert("created by", created_by.as_str());
+        }
+        if let Some(creation_date) = self.creation_date {
+            root.insert("creation date", creation_date);
+        }
+        root.insert("info", info);
+
+        Ok(root.encode())
+    }
+}
+
+/// 使 piece 数量接近 TARGET_PIECES 的 2 的幂
+fn auto_piece_length(total_length: u64) -> u64 {
+    (total_length / TARGET_PIECES)
+        .next_power_of_two()
+        .clamp(MIN_PIECE_LENGTH, MAX_PIECE_LENGTH)
+}
+
+/// 递归收集目录中的所有文件
+///
+/// ancestors 是当前目录及其上级目录的规范路径，指向这些目录的符号链接会形成循环，跳过这样的目录。
+fn collect_files(
+    dir: &Path,
+    path: &mut Vec<String>,
+    ancestors: &mut Vec<PathBuf>,
+    files: &mut Vec<SourceFile>,
+) -> TorrentResult<()> {
+    let canonical = fs::canonicalize(dir).map_err(|e| io_error(dir, e))?;
+    if ancestors.contains(&canonical) {
+        return Ok(());
+    }
+    ancestors.push(canonical);
+
+    let entries = fs::read_dir(dir).map_err(|e| io_error(dir, e))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|e| io_error(dir, e))?;
+        let full_path = entry.path();
+        let metadata = fs::metadata(&full_path).map_err(|e| io_error(&full_path, e))?;
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|_| invalid_value("info.files", &format!("'{}' is not valid utf-8", full_path.display())))?;
+
+        path.push(name);
+        if metadata.is_dir() {
+            collect_files(&full_path, path, ancestors, files)?;
+        } else {
+            files.push(SourceFile {
+                full_path,
+                path: path.clone(),
+                length: metadata.len(),
+            });
+        }
+        path.pop();
+    }
+    ancestors.pop();
+
+    Ok(())
+}
+
+/// 把所有文件看作一个连续的数据流，计算每个 piece 的 SHA-1
+fn hash_pieces(files: &[SourceFile], piece_length: u64) -> TorrentResult<Vec<u8>> {
+    let mut pieces = Vec::new();
+    let mut piece = vec![0u8; piece_length as usize];
+    let mut filled = 0;
+
+    for file in files {
+        let mut reader = fs::File::open(&file.full_path).map_err(|e| io_error(&file.full_path, e))?;
+        let mut read_length = 0u64;
+
+        loop {
+            let n = match reader.read(&mut piece[filled..]) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(io_error(&file.full_path, e)),
+            };
+
+            read_length += n as u64;
+            filled += n;
+            if filled == piece.len() {
+                pieces.extend_from_slice(&Sha1::digest(&piece));
+                filled = 0;
+            }
+        }
+
+        if read_length != file.length {
+            return Err(io_error(&file.full_path, io::Error::other("file was modified while hashing")));
+        }
+    }
+
+    if filled > 0 {
+        pieces.extend_from_slice(&Sha1::digest(&piece[..filled]));
+    }
+
+    Ok(pieces)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::temp_dir;
+    use crate::torrent::{FileEntry, FileLayout, Metainfo};
+    use crate::{FieldError, TorrentError};
+
+    use super::*;
+
+    /// 在临时目录中创建测试文件，返回根目录
+    fn create_files(test_name: &str, files: &[(&str, Vec<u8>)]) -> PathBuf {
+        let root = temp_dir(test_name);
+
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        root
+    }
+
+    #[test]
+    fn test_build_directory() {
+        let a = vec![1u8; 10];
+        let b = (0..40000u32).map(|i| i as u8).collect::<Vec<_>>();
+        let root = create_files("build-dir", &[("data/sub/b.bin", b.clone()), ("data/a.txt", a.clone())]);
+
+        let buffer = TorrentBuilder::new(root.join("data"))
+            .with_piece_length(16384)
+            .unwrap()
+            .with_announce("http://t1/announce")
+            .with_announce_list(vec![vec!["http://t1/announce".into()], vec!["udp://t2".into()]])
+            .with_comment("hello")
+            .with_creation_date(Some(1700000000))
+            .with_private(true)
+            .with_source("site")
+            .build()
+            .unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let metainfo = Metainfo::from_bytes(buffer.clone()).unwrap();
+        assert_eq!(Some("http://t1/announce"), metainfo.announce.as_deref());
+        assert_eq!(2, metainfo.announce_list.len());
+        assert_eq!(Some("hello"), metainfo.comment.as_deref());
+        assert!(metainfo.created_by.unwrap().starts_with("ez-bencoding/"));
+        assert_eq!(Some(1700000000), metainfo.creation_date);
+
+        let info = metainfo.info;
+        assert_eq!("data", info.name);
+        assert!(info.private);
+        assert_eq!(Some("site"), info.source.as_deref());
+        assert_eq!(
+            Some(FileLayout::Multiple {
+                files: vec![
+                    FileEntry { length: 10, path: vec!["a.txt".into()], attr: None },
+                    FileEntry { length: 40000, path: vec!["sub".into(), "b.bin".into()], attr: None },
+                ]
+            }),
+            info.layout
+        );
+
+        // piece 跨越文件边界
+        let data = [a, b].concat();
+        let pieces = data.chunks(16384).map(|piece| Sha1::digest(piece).into()).collect::<Vec<[u8; 20]>>();
+        assert_eq!(3, pieces.len());
+        assert_eq!(pieces, info.pieces);
+
+        // 输出是规范编码的
+        assert!(crate::BdecodeNode::parse_buffer_strict(buffer).is_ok());
+    }
+
+    #[test]
+    fn test_build_single_file() {
+        let root = create_files("build-file", &[("a.txt", vec![7u8; 100])]);
+
+        let buffer = TorrentBuilder::new(root.join("a.txt"))
+            .with_created_by(None)
+            .with_creation_date(None)
+            .build()
+            .unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let metainfo = Metainfo::from_bytes(buffer).unwrap();
+        assert_eq!(None, metainfo.announce);
+        assert_eq!(None, metainfo.created_by);
+        assert_eq!(None, metainfo.creation_date);
+        assert_eq!("a.txt", metainfo.info.name);
+        assert_eq!(MIN_PIECE_LENGTH, metainfo.info.piece_length);
+        assert_eq!(Some(FileLayout::Single { length: 100 }), metainfo.info.layout);
+        assert_eq!(vec![<[u8; 20]>::from(Sha1::digest([7u8; 100]))], metainfo.info.pieces);
+        assert!(!metainfo.info.private);
+    }
+
+    #[test]
+    fn test_auto_piece_length() {
+        assert_eq!(MIN_PIECE_LENGTH, auto_piece_length(0));
+        assert_eq!(MIN_PIECE_LENGTH, auto_piece_length(1500 * 16 * 1024));
+        assert_eq!(64 * 1024, auto_piece_length(1500 * 40 * 1024));
+        assert_eq!(MAX_PIECE_LENGTH, auto_piece_length(u64::MAX / 2));
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let root = create_files("build-errors", &[("a.txt", vec![1u8; 10])]);
+        fs::create_dir_all(root.join("empty")).unwrap();
+
+        for piece_length in [0, 20000, 8192, 128 * 1024 * 1024] {
+            let err = TorrentBuilder::new(root.join("a.txt")).with_piece_length(piece_length).unwrap_err();
+            assert!(matches!(err, TorrentError::Field(FieldError::InvalidValue { field, .. }) if field == "info.piece length"));
+        }
+
+        let err = TorrentBuilder::new(root.join("empty")).build().unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidValue { field, .. }) if field == "info.files"));
+
+        let err = TorrentBuilder::new(root.join("missing")).build().unwrap_err();
+        assert!(matches!(err, TorrentError::Io { .. }));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_build_symlink_loop() {
+        let root = create_files("build-symlink-loop", &[("data/sub/a.txt", vec![1u8; 10])]);
+        // data/sub/loop -> data
+        std::os::unix::fs::symlink(root.join("data"), root.join("data/sub/loop")).unwrap();
+
+        let buffer = TorrentBuilder::new(root.join("data")).build().unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        let metainfo = Metainfo::from_bytes(buffer).unwrap();
+        assert_eq!(
+            Some(FileLayout::Multiple {
+                files: vec![FileEntry { length: 10, path: vec!["sub".into(), "a.txt".into()], attr: None }]
+            }),
+            metainfo.info.layout
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{BdecodeNode, Dict, FieldError, TorrentResult};
+
+use crate::field::{expect_bytes, expect_dict, expect_length, invalid_value, optional, required};
+
+use super::check_path_component;
+
+/// v2 piece layer 中每个 hash 的长度
+pub const V2_HASH_LEN: usize = 32;
+
+/// v2 (BEP 52) file tree 展开后的一个文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileTreeEntry {
+    /// 文件相对于 name 目录的路径
+    pub path: Vec<String>,
+
+    pub length: u64,
+
+    /// 文件的 merkle tree 根节点的 SHA-256 hash, 空文件没有 pieces root
+    pub pieces_root: Option<[u8; V2_HASH_LEN]>,
+
+    /// 文件在 piece layers 中对应的 hash 列表，文件长度不超过 piece length 时没有 piece layer
+    pub piece_layer: Option<Vec<[u8; V2_HASH_LEN]>>,
+}
+
+impl FileTreeEntry {
+    /// 文件占用的 piece 数量
+    pub fn num_pieces(&self, piece_length: u64) -> u64 {
+        self.length.div_ceil(piece_length)
+    }
+}
+
+/// 按 dict key 的顺序展开 file tree
+pub(crate) fn flatten_file_tree<B: AsRef<[u8]>>(tree: &Dict<B>, field: &str) -> TorrentResult<Vec<FileTreeEntry>> {
+    let mut entries = Vec::new();
+    let mut path = Vec::new();
+    walk_file_tree(tree, field, &mut path, &mut entries)?;
+
+    if entries.is_empty() {
+        return Err(invalid_value(field, "file tree is empty").into());
+    }
+
+    Ok(entries)
+}
+
+fn walk_file_tree<B: AsRef<[u8]>>(
+    dir: &Dict<B>,
+    field: &str,
+    path: &mut Vec<String>,
+    entries: &mut Vec<FileTreeEntry>,
+) -> TorrentResult<()> {
+    for i in 0..dir.len() {
+        let (key, val) = dir.item(i);
+        let key_field = format!("{field}.{}", String::from_utf8_lossy(&key.as_str()));
+
+        let name = String::from_utf8(key.as_str().into_owned())
+            .map_err(|_| invalid_value(&key_field, "invalid utf-8"))?;
+        check_path_component(&name, &key_field)?;
+
+        let node = expect_dict(&val, &key_field)?;
+        path.push(name);
+
+        // 文件节点是只有一个 "" key 的 dict, 否则是目录
+        match optional(&node, "") {
+            Some(file) if node.len() == 1 => {
+                let file_field = format!("{key_field}.");
+                let file = expect_dict(&file, &file_field)?;
+                entries.push(read_file(&file, &file_field, path.clone())?);
+            }
+            Some(_) => return Err(invalid_value(&key_field, "file entry has sibling keys").into()),
+            None => walk_file_tree(&node, &key_field, path, entries)?,
+        }
+
+        path.pop();
+    }
+
+    Ok(())
+}
+
+fn read_file<B: AsRef<[u8]>>(file: &Dict<B>, field: &str, path: Vec<String>) -> TorrentResult<FileTreeEntry> {
+    let length = expect_length(&required(file, field, "length")?, &format!("{field}.length"))?;
+
+    let pieces_root = match optional(file, "pieces root") {
+        Some(node) => {
+            let root_field = format!("{field}.pieces root");
+            let root = expect_bytes(&node, &root_field)?;
+            let root = root
+                .try_into()
+                .map_err(|_| invalid_value(&root_field, "length is not 32"))?;
+
+            Some(root)
+        }
+        None if length > 0 => return Err(FieldError::MissingField(format!("{field}.pieces root")).into()),
+        None => None,
+    };
+
+    Ok(FileTreeEntry {
+        path,
+        length,
+        pieces_root,
+        piece_layer: None,
+    })
+}
+
+/// 为每个文件配对 piece layers 中对应的 hash 列表
+pub(crate) fn attach_piece_layers<B: AsRef<[u8]>>(
+    entries: &mut [FileTreeEntry],
+    piece_layers: Option<&BdecodeNode<B>>,
+    piece_length: u64,
+) -> TorrentResult<()> {
+    let piece_layers = piece_layers
+        .map(|node| expect_dict(node, "piece layers"))
+        .transpose()?;
+
+    for entry in entries.iter_mut() {
+        let Some(pieces_root) = entry.pieces_root else {
+            continue;
+        };
+
+        // 只有一个 piece 的文件，pieces root 就是 piece 的 hash
+        if entry.length <= piece_length {
+            continue;
+        }
+
+        let field = format!("piece layers.{}", entry.path.join("/"));
+        let Some(layer) = piece_layers.as_ref().and_then(|layers| layers.find(&pieces_root)) else {
+            return Err(FieldError::MissingField(field).into());
+        };
+
+        let layer = expect_bytes(&layer, &field)?;
+        if layer.len() % V2_HASH_LEN != 0 {
+            return Err(invalid_value(&field, "length is not a multiple of 32").into());
+        }
+        if (layer.len() / V2_HASH_LEN) as u64 != entry.num_pieces(piece_length) {
+            return Err(invalid_value(&field, "number of hashes does not match file length").into());
+        }
+
+        let layer = layer
+            .chunks_exact(V2_HASH_LEN)
+            .map(|hash| hash.try_into().expect("chunk size is 32"))
+            .collect();
+        entry.piece_layer = Some(layer);
+    }
+
+    Ok(())
+}
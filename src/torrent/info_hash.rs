@@ -0,0 +1,97 @@
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+use crate::field::{expect_dict, required};
+use crate::{BdecodeNode, TorrentResult};
+
+/// torrent 的 info-hash
+///
+/// 对 info dict 在 buffer 中的原始字节计算 hash, 而不是重新编码后再计算，
+/// 所以即使 torrent 不是规范（canonical）编码的，结果也与其他客户端一致。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InfoHashes {
+    /// v1 的 info-hash, 即 info dict 的 SHA-1
+    pub v1: [u8; 20],
+
+    /// v2 (BEP 52) 的 info-hash, 即 info dict 的 SHA-256
+    pub v2: [u8; 32],
+}
+
+impl InfoHashes {
+    /// 计算 root 节点中 info dict 的 info-hash
+    pub fn from_node<B: AsRef<[u8]>>(root: &BdecodeNode<B>) -> TorrentResult<Self> {
+        let info = info_bytes(root)?;
+
+        Ok(Self {
+            v1: Sha1::digest(info).into(),
+            v2: Sha256::digest(info).into(),
+        })
+    }
+
+    /// 截断为 20 字节的 v2 info-hash, 用于 tracker 和 DHT 等只支持 20 字节 hash 的场景
+    pub fn v2_truncated(&self) -> [u8; 20] {
+        self.v2[..20].try_into().expect("v2 info-hash is 32 bytes")
+    }
+}
+
+/// 计算 v1 的 info-hash (SHA-1)
+pub fn info_hash_v1<B: AsRef<[u8]>>(root: &BdecodeNode<B>) -> TorrentResult<[u8; 20]> {
+    Ok(Sha1::digest(info_bytes(root)?).into())
+}
+
+/// 计算 v2 的 info-hash (SHA-256)
+pub fn info_hash_v2<B: AsRef<[u8]>>(root: &BdecodeNode<B>) -> TorrentResult<[u8; 32]> {
+    Ok(Sha256::digest(info_bytes(root)?).into())
+}
+
+/// 获取 info dict 在 buffer 中的原始字节
+fn info_bytes<B: AsRef<[u8]>>(root: &BdecodeNode<B>) -> TorrentResult<&[u8]> {
+    let root_dict = expect_dict(root, "")?;
+    let info = required(&root_dict, "", "info")?;
+    expect_dict(&info, "info")?;
+
+    // info 节点与 root 节点共享同一个 buffer
+    Ok(&root.buffer_bytes()[info.byte_range()])
+}
+
+/// 将 hash 转换为小写的十六进制字符串
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FieldError, TorrentError};
+
+    use super::*;
+
+    #[test]
+    fn test_info_hashes() {
+        let root = BdecodeNode::parse_buffer(include_bytes!("../../res.torrent").to_vec()).unwrap();
+        let hashes = InfoHashes::from_node(&root).unwrap();
+
+        assert_eq!("c7ea56485743ad9ddbe26bd8e03e795549603227", to_hex(&hashes.v1));
+        assert_eq!(
+            "daff95ca9a788ed178f39cadbb04103787821dcdd1e7bc6dd220f85b6596f889",
+            to_hex(&hashes.v2)
+        );
+        assert_eq!("daff95ca9a788ed178f39cadbb04103787821dcd", to_hex(&hashes.v2_truncated()));
+        assert_eq!(hashes.v1, info_hash_v1(&root).unwrap());
+        assert_eq!(hashes.v2, info_hash_v2(&root).unwrap());
+    }
+
+    #[test]
+    fn test_info_hash_errors() {
+        let root = BdecodeNode::parse_buffer(b"d8:announce3:urle".to_vec()).unwrap();
+        let err = info_hash_v1(&root).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::MissingField(field)) if field == "info"));
+
+        let root = BdecodeNode::parse_buffer(b"d4:infoi1ee".to_vec()).unwrap();
+        let err = info_hash_v2(&root).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidType { expected: "dict", .. })));
+
+        let root = BdecodeNode::parse_buffer(b"le".to_vec()).unwrap();
+        let err = InfoHashes::from_node(&root).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidType { expected: "dict", .. })));
+    }
+}
@@ -0,0 +1,522 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+use crate::field::invalid_value;
+use crate::TorrentResult;
+
+use super::{
+    check_path_component, io_error, FileLayout, Info, Metainfo, PIECE_LENGTH_LIMIT, V2_HASH_LEN,
+};
+
+/// v2 merkle tree 叶子节点对应的数据块大小
+pub const V2_BLOCK_SIZE: u64 = 16 * 1024;
+
+/// piece 或文件的校验结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyStatus {
+    Pass,
+    Fail,
+
+    /// 文件不存在或长度不足
+    Missing,
+}
+
+/// 一个文件的校验结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStatus {
+    /// 文件在磁盘上的路径
+    pub path: PathBuf,
+
+    pub length: u64,
+
+    /// 文件本身缺失时为 Missing; v1 中与文件重叠的 piece 全部通过,
+    /// 并且 v2 的 pieces root 匹配时为 Pass, 否则为 Fail
+    pub status: VerifyStatus,
+}
+
+/// 校验报告
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// v1 每个 piece 的结果，v2-only torrent 中为空
+    pub pieces: Vec<VerifyStatus>,
+
+    /// 每个文件的结果，不包含 BEP 47 的 padding 文件
+    pub files: Vec<FileStatus>,
+}
+
+impl VerifyReport {
+    /// 是否所有 piece 和文件都通过校验
+    pub fn is_ok(&self) -> bool {
+        self.pieces.iter().chain(self.files.iter().map(|f| &f.status)).all(|s| *s == VerifyStatus::Pass)
+    }
+}
+
+/// v1 文件及其在数据流中的位置
+struct V1File {
+    path: PathBuf,
+    length: u64,
+    is_padding: bool,
+    missing: bool,
+}
+
+/// 校验 base_dir 中的数据，多文件 torrent 的数据位于 base_dir/name 目录下
+pub fn verify(metainfo: &Metainfo, base_dir: &Path) -> TorrentResult<VerifyReport> {
+    let info = &metainfo.info;
+    // 校验时按 piece length 分配缓冲区
+    if !info.piece_length.is_power_of_two() || info.piece_length > PIECE_LENGTH_LIMIT {
+        return Err(invalid_value("info.piece length", "must be a power of two and at most 64 MiB to verify").into());
+    }
+
+    // Metainfo 的字段是公开的，可能不是解析得到的，拼接前再检查一次路径，避免访问 base_dir 之外的文件
+    check_path_component(&info.name, "info.name")?;
+    let single_file = is_single_file(info);
+    let data_path = |path: &[String]| -> TorrentResult<PathBuf> {
+        if single_file {
+            return Ok(base_dir.join(&info.name));
+        }
+
+        path.iter().try_fold(base_dir.join(&info.name), |full, p| {
+            check_path_component(p, "info.files")?;
+            Ok(full.join(p))
+        })
+    };
+
+    let mut pieces = Vec::new();
+    let mut files = Vec::new();
+
+    if info.is_v1() {
+        let mut v1_files = info
+            .files()
+            .into_iter()
+            .map(|f| {
+                Ok(V1File {
+                    path: data_path(&f.path)?,
+                    length: f.length,
+                    is_padding: f.is_padding(),
+                    missing: false,
+                })
+            })
+            .collect::<TorrentResult<Vec<_>>>()?;
+        pieces = verify_pieces(info, &mut v1_files)?;
+
+        let mut offset = 0;
+        for file in v1_files {
+            let start = offset;
+            offset += file.length;
+            if file.is_padding {
+                continue;
+            }
+
+            let status = if file.missing {
+                VerifyStatus::Missing
+            } else if file.length == 0 {
+                VerifyStatus::Pass
+            } else {
+                let first = (start / info.piece_length) as usize;
+                let last = ((offset - 1) / info.piece_length) as usize;
+                match pieces[first..=last].iter().all(|s| *s == VerifyStatus::Pass) {
+                    true => VerifyStatus::Pass,
+                    false => VerifyStatus::Fail,
+                }
+            };
+
+            files.push(FileStatus {
+                path: file.path,
+                length: file.length,
+                status,
+            });
+        }
+    }
+
+    if let Some(file_tree) = &info.file_tree {
+        // hybrid torrent 中按路径查找 v1 的结果
+        let v1_files = files
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.path.clone(), i))
+            .collect::<HashMap<_, _>>();
+
+        for (i, entry) in file_tree.iter().enumerate() {
+            let path = data_path(&entry.path)?;
+            let status = match entry.pieces_root {
+                Some(pieces_root) => match merkle_root(&path, entry.length)? {
+                    Some(root) if root == pieces_root => VerifyStatus::Pass,
+                    Some(_) => VerifyStatus::Fail,
+                    None => VerifyStatus::Missing,
+                },
+                None if path.exists() => VerifyStatus::Pass,
+                None => VerifyStatus::Missing,
+            };
+
+            // hybrid torrent 中合并 v1 的结果
+            match v1_files.get(&path).map(|i| &mut files[*i]) {
+                Some(file) if file.status == VerifyStatus::Pass => file.status = status,
+                Some(_) => {}
+                None if info.is_v1() => {
+                    return Err(invalid_value(&format!("info.file tree[{i}]"), "file is not in the v1 file list").into())
+                }
+                None => files.push(FileStatus {
+                    path,
+                    length: entry.length,
+                    status,
+                }),
+            }
+        }
+    }
+
+    // 只读取了 torrent 中记录的长度，文件比记录的长时也不匹配
+    for file in files.iter_mut().filter(|f| f.status == VerifyStatus::Pass) {
+        let metadata = fs::metadata(&file.path).map_err(|e| io_error(&file.path, e))?;
+        if metadata.len() != file.length {
+            file.status = VerifyStatus::Fail;
+        }
+    }
+
+    Ok(VerifyReport { pieces, files })
+}
+
+/// 单文件 torrent 的数据直接位于 base_dir/name
+fn is_single_file(info: &Info) -> bool {
+    match (&info.layout, &info.file_tree) {
+        (Some(layout), _) => matches!(layout, FileLayout::Single { .. }),
+        (None, Some(file_tree)) => file_tree.len() == 1 && file_tree[0].path == [info.name.clone()],
+        (None, None) => false,
+    }
+}
+
+/// 把所有文件看作一个连续的数据流，逐个 piece 校验 SHA-1
+fn verify_pieces(info: &Info, files: &mut [V1File]) -> TorrentResult<Vec<VerifyStatus>> {
+    let mut statuses = Vec::with_capacity(info.pieces.len());
+    let mut piece = vec![0u8; info.piece_length as usize];
+    let mut filled = 0;
+    let mut piece_missing = false;
+
+    let mut finish_piece = |data: &[u8], missing: bool| {
+        let status = match info.pieces.get(statuses.len()) {
+            _ if missing => VerifyStatus::Missing,
+            Some(hash) if Sha1::digest(data).as_slice() == hash => VerifyStatus::Pass,
+            _ => VerifyStatus::Fail,
+        };
+        statuses.push(status);
+    };
+
+    for file in files.iter_mut() {
+        let mut reader = match file.is_padding {
+            true => None,
+            false => match fs::File::open(&file.path) {
+                Ok(reader) => Some(reader),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    file.missing = true;
+                    None
+                }
+                Err(e) => return Err(io_error(&file.path, e)),
+            },
+        };
+
+        let mut remaining = file.length;
+        while remaining > 0 {
+            let want = remaining.min((piece.len() - filled) as u64) as usize;
+            let buf = &mut piece[filled..filled + want];
+
+            let read = match reader.as_mut() {
+                Some(reader) => read_full(reader, buf).map_err(|e| io_error(&file.path, e))?,
+                None => 0,
+            };
+            if read < want {
+                // padding 文件的内容全部为 0
+                buf[read..].fill(0);
+                if !file.is_padding {
+                    file.missing = true;
+                    piece_missing = true;
+                    reader = None;
+                }
+            }
+
+            filled += want;
+            remaining -= want as u64;
+            if filled == piece.len() {
+                finish_piece(&piece, piece_missing);
+                filled = 0;
+                piece_missing = false;
+            }
+        }
+    }
+
+    if filled > 0 {
+        finish_piece(&piece[..filled], piece_missing);
+    }
+
+    Ok(statuses)
+}
+
+/// 计算文件的 v2 merkle root, 文件不存在或长度不足时返回 None
+fn merkle_root(path: &Path, length: u64) -> TorrentResult<Option<[u8; V2_HASH_LEN]>> {
+    let mut reader = match fs::File::open(path) {
+        Ok(reader) => reader,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(io_error(path, e)),
+    };
+
+    // length 来自 torrent 文件，不按它预先分配，读到多少数据块就保存多少叶子节点
+    let mut leaves = Vec::new();
+    let mut block = vec![0u8; V2_BLOCK_SIZE as usize];
+    let mut remaining = length;
+    while remaining > 0 {
+        let want = remaining.min(V2_BLOCK_SIZE) as usize;
+        if read_full(&mut reader, &mut block[..want]).map_err(|e| io_error(path, e))? < want {
+            return Ok(None);
+        }
+
+        leaves.push(<[u8; V2_HASH_LEN]>::from(Sha256::digest(&block[..want])));
+        remaining -= want as u64;
+    }
+
+    Ok(Some(merkle_root_of(leaves)))
+}
+
+/// 由叶子节点计算 merkle root, 叶子数量不足 2 的幂时用全 0 的 hash 补齐
+fn merkle_root_of(mut layer: Vec<[u8; V2_HASH_LEN]>) -> [u8; V2_HASH_LEN] {
+    layer.resize(layer.len().next_power_of_two(), [0u8; V2_HASH_LEN]);
+
+    while layer.len() > 1 {
+        layer = layer
+            .chunks_exact(2)
+            .map(|pair| {
+                let mut hasher = Sha256::new();
+                hasher.update(pair[0]);
+                hasher.update(pair[1]);
+                hasher.finalize().into()
+            })
+            .collect();
+    }
+
+    layer[0]
+}
+
+/// 尽量读满 buf, 返回实际读取的字节数
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::temp_dir;
+    use crate::torrent::{FileEntry, TorrentBuilder};
+    use crate::{FieldError, TorrentError};
+    use crate::BencodeValue;
+
+    use super::*;
+
+    #[test]
+    fn test_verify_v1() {
+        let base = temp_dir("verify-v1");
+        let data = base.join("data");
+        fs::create_dir_all(data.join("sub")).unwrap();
+        fs::write(data.join("a.txt"), vec![1u8; 10000]).unwrap();
+        fs::write(data.join("sub/b.bin"), vec![2u8; 30000]).unwrap();
+        fs::write(data.join("c.bin"), vec![3u8; 20000]).unwrap();
+
+        let buffer = TorrentBuilder::new(&data).with_piece_length(16384).unwrap().build().unwrap();
+        let metainfo = Metainfo::from_bytes(buffer).unwrap();
+
+        let report = verify(&metainfo, &base).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(4, report.pieces.len());
+        assert_eq!(data.join("sub/b.bin"), report.files[2].path);
+
+        // 文件顺序为 a.txt(0..10000), c.bin(10000..30000), sub/b.bin(30000..60000)
+        let mut c = vec![3u8; 20000];
+        c[19999] = 0;
+        fs::write(data.join("c.bin"), c).unwrap();
+        fs::remove_file(data.join("sub/b.bin")).unwrap();
+
+        let report = verify(&metainfo, &base).unwrap();
+        fs::remove_dir_all(&base).unwrap();
+
+        assert!(!report.is_ok());
+        assert_eq!(
+            vec![VerifyStatus::Pass, VerifyStatus::Missing, VerifyStatus::Missing, VerifyStatus::Missing],
+            report.pieces
+        );
+        let statuses = report.files.iter().map(|f| f.status).collect::<Vec<_>>();
+        assert_eq!(vec![VerifyStatus::Pass, VerifyStatus::Fail, VerifyStatus::Missing], statuses);
+    }
+
+    #[test]
+    fn test_verify_v1_single_file() {
+        let base = temp_dir("verify-single");
+        fs::write(base.join("a.txt"), vec![1u8; 20000]).unwrap();
+
+        let buffer = TorrentBuilder::new(base.join("a.txt")).with_piece_length(16384).unwrap().build().unwrap();
+        let metainfo = Metainfo::from_bytes(buffer).unwrap();
+        assert!(verify(&metainfo, &base).unwrap().is_ok());
+
+        let mut data = vec![1u8; 20000];
+        data[0] = 0;
+        fs::write(base.join("a.txt"), data).unwrap();
+        let report = verify(&metainfo, &base).unwrap();
+        assert_eq!(vec![VerifyStatus::Fail, VerifyStatus::Pass], report.pieces);
+        assert_eq!(VerifyStatus::Fail, report.files[0].status);
+
+        // 文件末尾有多余的数据
+        fs::write(base.join("a.txt"), vec![1u8; 20001]).unwrap();
+        let report = verify(&metainfo, &base).unwrap();
+        fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(vec![VerifyStatus::Pass, VerifyStatus::Pass], report.pieces);
+        assert_eq!(VerifyStatus::Fail, report.files[0].status);
+    }
+
+    #[test]
+    fn test_verify_invalid_metainfo() {
+        let base = temp_dir("verify-invalid-metainfo");
+        let data = base.join("data");
+        fs::create_dir_all(&data).unwrap();
+        fs::write(data.join("a.txt"), vec![1u8; 10]).unwrap();
+
+        let buffer = TorrentBuilder::new(&data).build().unwrap();
+        let mut metainfo = Metainfo::from_bytes(buffer).unwrap();
+        metainfo.info.layout = Some(FileLayout::Multiple {
+            files: vec![FileEntry {
+                length: 10,
+                path: vec!["..".into(), "etc".into(), "passwd".into()],
+                attr: None,
+            }],
+        });
+
+        let err = verify(&metainfo, &base).unwrap_err();
+        assert!(matches!(err, TorrentError::Field(FieldError::InvalidValue { field, .. }) if field == "info.files"));
+
+        // 解析时接受任意正数的 v1 piece length, 但校验时会按 piece length 分配缓冲区
+        metainfo.info.layout = Some(FileLayout::Single { length: 10 });
+        for piece_length in [10000, 128 * 1024 * 1024] {
+            metainfo.info.piece_length = piece_length;
+            let err = verify(&metainfo, &base).unwrap_err();
+            assert!(matches!(err, TorrentError::Field(FieldError::InvalidValue { field, .. }) if field == "info.piece length"));
+        }
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_merkle_root() {
+        let h = |data: &[u8]| <[u8; 32]>::from(Sha256::digest(data));
+        let h2 = |a: [u8; 32], b: [u8; 32]| h(&[a, b].concat());
+
+        let data = (0..40000u32).map(|i| i as u8).collect::<Vec<_>>();
+        let leaves = data.chunks(16384).map(h).collect::<Vec<_>>();
+        let expected = h2(h2(leaves[0], leaves[1]), h2(leaves[2], [0u8; 32]));
+        assert_eq!(expected, merkle_root_of(leaves.clone()));
+        assert_eq!(leaves[0], merkle_root_of(vec![leaves[0]]));
+    }
+
+    #[test]
+    fn test_verify_v2() {
+        let base = temp_dir("verify-v2");
+        let data = (0..40000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let leaves = data.chunks(16384).map(|b| Sha256::digest(b).into()).collect();
+        let pieces_root = merkle_root_of(leaves);
+
+        let mut file = BencodeValue::new_dict();
+        file.insert("length", data.len() as i64);
+        file.insert("pieces root", pieces_root.as_slice());
+        let mut node = BencodeValue::new_dict();
+        node.insert("", file);
+        let mut empty = BencodeValue::new_dict();
+        empty.insert("length", 0);
+        let mut empty_node = BencodeValue::new_dict();
+        empty_node.insert("", empty);
+
+        let mut tree = BencodeValue::new_dict();
+        tree.insert("a.bin", node);
+        tree.insert("empty", empty_node);
+
+        let mut info = BencodeValue::new_dict();
+        info.insert("name", "data");
+        info.insert("piece length", 65536);
+        info.insert("meta version", 2);
+        info.insert("file tree", tree);
+        let mut root = BencodeValue::new_dict();
+        root.insert("info", info);
+        let metainfo = Metainfo::from_bytes(root.encode()).unwrap();
+
+        fs::create_dir_all(base.join("data")).unwrap();
+        fs::write(base.join("data/a.bin"), &data).unwrap();
+        let report = verify(&metainfo, &base).unwrap();
+        assert!(report.pieces.is_empty());
+        let statuses = report.files.iter().map(|f| f.status).collect::<Vec<_>>();
+        assert_eq!(vec![VerifyStatus::Pass, VerifyStatus::Missing], statuses);
+
+        fs::write(base.join("data/empty"), b"").unwrap();
+        assert!(verify(&metainfo, &base).unwrap().is_ok());
+
+        fs::write(base.join("data/a.bin"), &data[..30000]).unwrap();
+        let report = verify(&metainfo, &base).unwrap();
+        assert_eq!(VerifyStatus::Missing, report.files[0].status);
+
+        let mut corrupted = data.clone();
+        corrupted[39999] ^= 1;
+        fs::write(base.join("data/a.bin"), &corrupted).unwrap();
+        let report = verify(&metainfo, &base).unwrap();
+        fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(VerifyStatus::Fail, report.files[0].status);
+    }
+
+    #[test]
+    fn test_verify_padding_attr() {
+        let base = temp_dir("verify-padding-attr");
+        let a = vec![1u8; 10000];
+        let b = vec![2u8; 5000];
+        let mut piece = a.clone();
+        piece.resize(16384, 0);
+        let pieces = [Sha1::digest(&piece), Sha1::digest(&b)].concat();
+
+        let file = |length: i64, path: &str, attr: Option<&str>| {
+            let mut file = BencodeValue::new_dict();
+            file.insert("length", length);
+            file.insert("path", vec![BencodeValue::from(path)]);
+            if let Some(attr) = attr {
+                file.insert("attr", attr);
+            }
+            file
+        };
+
+        // padding 文件由 attr 标记，路径不在 .pad 目录下
+        let mut info = BencodeValue::new_dict();
+        info.insert("name", "data");
+        info.insert("piece length", 16384);
+        info.insert("pieces", pieces);
+        info.insert(
+            "files",
+            vec![file(10000, "a.bin", Some("x")), file(6384, "pad", Some("p")), file(5000, "b.bin", None)],
+        );
+        let mut root = BencodeValue::new_dict();
+        root.insert("info", info);
+        let metainfo = Metainfo::from_bytes(root.encode()).unwrap();
+
+        fs::create_dir_all(base.join("data")).unwrap();
+        fs::write(base.join("data/a.bin"), &a).unwrap();
+        fs::write(base.join("data/b.bin"), &b).unwrap();
+        let report = verify(&metainfo, &base).unwrap();
+        fs::remove_dir_all(&base).unwrap();
+
+        assert!(report.is_ok());
+        let paths = report.files.iter().map(|f| f.path.clone()).collect::<Vec<_>>();
+        assert_eq!(vec![base.join("data/a.bin"), base.join("data/b.bin")], paths);
+    }
+}
@@ -0,0 +1,399 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::compact::{parse_compact_addrs, COMPACT_V4_LEN, COMPACT_V6_LEN};
+use crate::field::{expect_bytes, expect_dict, expect_length, expect_list, expect_string, invalid_value, optional, required, FieldResult};
+use crate::torrent::to_hex;
+use crate::{BdecodeNode, BencodeValue, List, TrackerResult};
+
+/// info-hash 的长度
+const INFO_HASH_LEN: usize = 20;
+
+/// HTTP tracker 对 announce 请求的回复
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackerResponse {
+    Success(AnnounceResponse),
+
+    /// 回复中包含 failure reason 时，其他字段都没有意义
+    Failure { reason: String },
+}
+
+/// 成功的 announce 回复
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnnounceResponse {
+    /// 客户端下次 announce 前应该等待的秒数
+    pub interval: u64,
+
+    /// 两次 announce 之间的最小间隔秒数
+    pub min_interval: Option<u64>,
+
+    /// 做种者的数量
+    pub complete: Option<u64>,
+
+    /// 下载者的数量
+    pub incomplete: Option<u64>,
+
+    /// 之后的 announce 请求中需要带上的 tracker id
+    pub tracker_id: Option<Vec<u8>>,
+
+    pub warning_message: Option<String>,
+
+    /// peers 和 peers6 中的所有 peer 地址,
+    /// dict 格式的 peer 中 ip 是域名时无法表示为 SocketAddr, 会被跳过
+    pub peers: Vec<SocketAddr>,
+}
+
+impl TrackerResponse {
+    /// 从解析后的 root 节点中读取 tracker 的回复
+    pub fn from_node<B: AsRef<[u8]>>(root_node: &BdecodeNode<B>) -> TrackerResult<Self> {
+        let root = expect_dict(root_node, "")?;
+
+        if let Some(reason) = optional(&root, "failure reason") {
+            return Ok(TrackerResponse::Failure {
+                reason: expect_string(&reason, "failure reason")?,
+            });
+        }
+
+        let interval = expect_length(&required(&root, "", "interval")?, "interval")?;
+        let min_interval = optional(&root, "min interval")
+            .map(|node| expect_length(&node, "min interval"))
+            .transpose()?;
+        let complete = optional(&root, "complete")
+            .map(|node| expect_length(&node, "complete"))
+            .transpose()?;
+        let incomplete = optional(&root, "incomplete")
+            .map(|node| expect_length(&node, "incomplete"))
+            .transpose()?;
+        let tracker_id = optional(&root, "tracker id")
+            .map(|node| expect_bytes(&node, "tracker id"))
+            .transpose()?;
+        let warning_message = optional(&root, "warning message")
+            .map(|node| expect_string(&node, "warning message"))
+            .transpose()?;
+
+        // peers 可以是 dict 的 list, 也可以是 compact 格式 (BEP 23) 的字符串
+        let mut peers = match optional(&root, "peers") {
+            Some(node @ BdecodeNode::List(_)) => parse_peer_list(&expect_list(&node, "peers")?)?,
+            Some(node) => expect_compact_peers(&node, "peers", COMPACT_V4_LEN)?,
+            None => Vec::new(),
+        };
+        // IPv6 的 peer (BEP 7)
+        if let Some(node) = optional(&root, "peers6") {
+            peers.extend(expect_compact_peers(&node, "peers6", COMPACT_V6_LEN)?);
+        }
+
+        Ok(TrackerResponse::Success(AnnounceResponse {
+            interval,
+            min_interval,
+            complete,
+            incomplete,
+            tracker_id,
+            warning_message,
+            peers,
+        }))
+    }
+
+    /// 解析 HTTP 回复的 body 并读取 tracker 的回复
+    pub fn from_bytes(buffer: Vec<u8>) -> TrackerResult<Self> {
+        let root = BdecodeNode::parse_buffer(buffer)?;
+
+        Self::from_node(&root)
+    }
+}
+
+/// HTTP tracker 对 scrape 请求的回复 (BEP 48)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScrapeResponse {
+    /// 以 20 字节的原始 info-hash 为 key 的统计信息
+    pub files: BTreeMap<[u8; INFO_HASH_LEN], ScrapeStats>,
+
+    /// flags 中的 min_request_interval, 两次 scrape 之间的最小间隔秒数
+    pub min_request_interval: Option<u64>,
+}
+
+/// 一个 torrent 的统计信息
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScrapeStats {
+    /// 做种者的数量
+    pub complete: u64,
+
+    /// 完成下载的次数
+    pub downloaded: u64,
+
+    /// 下载者的数量
+    pub incomplete: u64,
+
+    /// torrent 的名称
+    pub name: Option<String>,
+}
+
+impl ScrapeResponse {
+    /// 从解析后的 root 节点中读取 scrape 的回复
+    pub fn from_node<B: AsRef<[u8]>>(root_node: &BdecodeNode<B>) -> TrackerResult<Self> {
+        let root = expect_dict(root_node, "")?;
+
+        let files_node = expect_dict(&required(&root, "", "files")?, "files")?;
+        let mut files = BTreeMap::new();
+        for i in 0..files_node.len() {
+            let (key, val) = files_node.item(i);
+
+            // key 是二进制的 info-hash, 字段路径中使用十六进制表示
+            let key = key.as_str();
+            let field = format!("files.{}", to_hex(&key));
+            let info_hash = key
+                .as_ref()
+                .try_into()
+                .map_err(|_| invalid_value(&field, "info-hash length is not 20"))?;
+
+            files.insert(info_hash, ScrapeStats::from_node(&val, &field)?);
+        }
+
+        let min_request_interval = match optional(&root, "flags") {
+            Some(flags) => optional(&expect_dict(&flags, "flags")?, "min_request_interval")
+                .map(|node| expect_length(&node, "flags.min_request_interval"))
+                .transpose()?,
+            None => None,
+        };
+
+        Ok(Self {
+            files,
+            min_request_interval,
+        })
+    }
+
+    /// 解析 HTTP 回复的 body 并读取 scrape 的回复
+    pub fn from_bytes(buffer: Vec<u8>) -> TrackerResult<Self> {
+        let root = BdecodeNode::parse_buffer(buffer)?;
+
+        Self::from_node(&root)
+    }
+
+    /// 编码为规范的 bencode
+    pub fn encode(&self) -> Vec<u8> {
+        let mut files = BencodeValue::new_dict();
+        for (info_hash, stats) in &self.files {
+            let mut file = BencodeValue::new_dict();
+            file.insert("complete", stats.complete as i64);
+            file.insert("downloaded", stats.downloaded as i64);
+            file.insert("incomplete", stats.incomplete as i64);
+            if let Some(name) = &stats.name {
+                file.insert("name", name.as_str());
+            }
+            files.insert(info_hash.as_slice(), file);
+        }
+
+        let mut root = BencodeValue::new_dict();
+        root.insert("files", files);
+        if let Some(interval) = self.min_request_interval {
+            let mut flags = BencodeValue::new_dict();
+            flags.insert("min_request_interval", interval as i64);
+            root.insert("flags", flags);
+        }
+
+        root.encode()
+    }
+}
+
+impl ScrapeStats {
+    fn from_node<B: AsRef<[u8]>>(node: &BdecodeNode<B>, field: &str) -> TrackerResult<Self> {
+        let stats = expect_dict(node, field)?;
+        let count = |key: &str| expect_length(&required(&stats, field, key)?, &format!("{field}.{key}"));
+
+        Ok(Self {
+            complete: count("complete")?,
+            downloaded: count("downloaded")?,
+            incomplete: count("incomplete")?,
+            name: optional(&stats, "name")
+                .map(|node| expect_string(&node, &format!("{field}.name")))
+                .transpose()?,
+        })
+    }
+}
+
+/// 解析 dict 格式的 peer 列表，每个 dict 包含 peer id, ip 和 port,
+/// ip 也可以是域名 (BEP 3), 这样的 peer 会被跳过
+fn parse_peer_list<B: AsRef<[u8]>>(peers: &List<B>) -> FieldResult<Vec<SocketAddr>> {
+    let mut addrs = Vec::with_capacity(peers.len());
+    for i in 0..peers.len() {
+        let field = format!("peers[{i}]");
+        let peer = expect_dict(&peers.item(i), &field)?;
+
+        let ip = expect_string(&required(&peer, &field, "ip")?, &format!("{field}.ip"))?;
+
+        let port_field = format!("{field}.port");
+        let port = expect_length(&required(&peer, &field, "port")?, &port_field)?;
+        let port = u16::try_from(port).map_err(|_| invalid_value(&port_field, "out of range"))?;
+
+        if let Ok(ip) = ip.parse::<IpAddr>() {
+            addrs.push(SocketAddr::new(ip, port));
+        }
+    }
+
+    Ok(addrs)
+}
+
+fn expect_compact_peers<B: AsRef<[u8]>>(node: &BdecodeNode<B>, field: &str, addr_len: usize) -> FieldResult<Vec<SocketAddr>> {
+    let peers = expect_bytes(node, field)?;
+
+    parse_compact_addrs(&peers, addr_len)
+        .ok_or_else(|| invalid_value(field, &format!("length is not a multiple of {addr_len}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BencodeValue, FieldError, TrackerError};
+
+    use super::*;
+
+    fn success(buffer: Vec<u8>) -> AnnounceResponse {
+        match TrackerResponse::from_bytes(buffer).unwrap() {
+            TrackerResponse::Success(response) => response,
+            TrackerResponse::Failure { reason } => panic!("unexpected failure: {reason}"),
+        }
+    }
+
+    #[test]
+    fn test_compact_peers() {
+        let mut root = BencodeValue::new_dict();
+        root.insert("interval", 1800);
+        root.insert("min interval", 900);
+        root.insert("complete", 5);
+        root.insert("incomplete", 3);
+        root.insert("tracker id", "abc");
+        root.insert("warning message", "slow down");
+        root.insert("peers", [1u8, 2, 3, 4, 0x1a, 0xe1, 5, 6, 7, 8, 0, 80].as_slice());
+        let mut peer6 = vec![0x20, 0x01, 0x0d, 0xb8];
+        peer6.extend_from_slice(&[0; 11]);
+        peer6.extend_from_slice(&[1, 0x1a, 0xe1]);
+        root.insert("peers6", peer6);
+
+        let response = success(root.encode());
+        assert_eq!(
+            AnnounceResponse {
+                interval: 1800,
+                min_interval: Some(900),
+                complete: Some(5),
+                incomplete: Some(3),
+                tracker_id: Some(b"abc".to_vec()),
+                warning_message: Some("slow down".into()),
+                peers: vec![
+                    "1.2.3.4:6881".parse().unwrap(),
+                    "5.6.7.8:80".parse().unwrap(),
+                    "[2001:db8::1]:6881".parse().unwrap(),
+                ],
+            },
+            response
+        );
+    }
+
+    #[test]
+    fn test_dict_peers() {
+        let buffer = b"d8:intervali60e5:peersld2:ip7:1.2.3.47:peer id20:abcdefghij01234567894:porti6881eed2:ip3:::14:porti80eeee";
+        let response = success(buffer.to_vec());
+        assert_eq!(60, response.interval);
+        assert_eq!(None, response.min_interval);
+        assert_eq!(
+            vec!["1.2.3.4:6881".parse::<SocketAddr>().unwrap(), "[::1]:80".parse().unwrap()],
+            response.peers
+        );
+
+        let response = success(b"d8:intervali60e5:peers0:e".to_vec());
+        assert!(response.peers.is_empty());
+    }
+
+    #[test]
+    fn test_dict_peers_hostname() {
+        // ip 是域名的 peer 被跳过，不影响其他 peer
+        let buffer = b"d8:intervali60e5:peersld2:ip7:1.2.3.44:porti6881eed2:ip16:peer.example.org4:porti6882eed2:ip3:::14:porti80eeee";
+        let response = success(buffer.to_vec());
+        assert_eq!(
+            vec!["1.2.3.4:6881".parse::<SocketAddr>().unwrap(), "[::1]:80".parse().unwrap()],
+            response.peers
+        );
+
+        // 域名 peer 的 port 仍然需要合法
+        let buffer = b"d8:intervali60e5:peersld2:ip4:host4:porti70000eeee";
+        let err = TrackerResponse::from_bytes(buffer.to_vec()).unwrap_err();
+        assert!(matches!(err, TrackerError::Field(FieldError::InvalidValue { field, .. }) if field == "peers[0].port"));
+    }
+
+    #[test]
+    fn test_failure() {
+        let response = TrackerResponse::from_bytes(b"d14:failure reason11:not allowede".to_vec()).unwrap();
+        assert_eq!(TrackerResponse::Failure { reason: "not allowed".into() }, response);
+    }
+
+    #[test]
+    fn test_scrape() {
+        // info-hash 中包含 '"', '\\' 和不可打印的字节
+        let hash_1 = *b"\x00\"\\aaaaaaaaaaaaaaaa\xff";
+        let hash_2 = [0x22u8; 20];
+
+        let mut response = ScrapeResponse {
+            min_request_interval: Some(600),
+            ..Default::default()
+        };
+        response.files.insert(
+            hash_1,
+            ScrapeStats {
+                complete: 5,
+                downloaded: 50,
+                incomplete: 10,
+                name: Some("a.txt".into()),
+            },
+        );
+        response.files.insert(hash_2, ScrapeStats::default());
+
+        let buffer = response.encode();
+        let root = BdecodeNode::parse_buffer_strict(buffer.clone()).unwrap();
+        assert_eq!(response, ScrapeResponse::from_node(&root).unwrap());
+
+        // to_json 中二进制的 key 可以与普通字符区分
+        let json = root.to_json();
+        assert!(json.contains(r#""\x00\x22\x5caaaaaaaaaaaaaaaa\xff": "#));
+
+        let response = ScrapeResponse::from_bytes(b"d5:filesdee".to_vec()).unwrap();
+        assert!(response.files.is_empty());
+        assert_eq!(None, response.min_request_interval);
+    }
+
+    #[test]
+    fn test_scrape_errors() {
+        let err = ScrapeResponse::from_bytes(b"de".to_vec()).unwrap_err();
+        assert!(matches!(err, TrackerError::Field(FieldError::MissingField(field)) if field == "files"));
+
+        let err = ScrapeResponse::from_bytes(b"d5:filesd3:abcdeee".to_vec()).unwrap_err();
+        assert!(matches!(err, TrackerError::Field(FieldError::InvalidValue { field, .. }) if field == "files.616263"));
+
+        let buffer = b"d5:filesd20:aaaaaaaaaaaaaaaaaaaad8:completei1e10:downloadedi1eeee";
+        let err = ScrapeResponse::from_bytes(buffer.to_vec()).unwrap_err();
+        assert!(matches!(err, TrackerError::Field(FieldError::MissingField(field)) if field.ends_with("6161.incomplete")));
+
+        let err = ScrapeResponse::from_bytes(b"d5:filesde5:flagsi1ee".to_vec()).unwrap_err();
+        assert!(matches!(err, TrackerError::Field(FieldError::InvalidType { field, .. }) if field == "flags"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = TrackerResponse::from_bytes(b"d5:peers0:e".to_vec()).unwrap_err();
+        assert!(matches!(err, TrackerError::Field(FieldError::MissingField(field)) if field == "interval"));
+
+        let err = TrackerResponse::from_bytes(b"d8:intervali-1ee".to_vec()).unwrap_err();
+        assert!(matches!(err, TrackerError::Field(FieldError::InvalidValue { field, .. }) if field == "interval"));
+
+        let err = TrackerResponse::from_bytes(b"d8:intervali60e5:peers5:abcdee".to_vec()).unwrap_err();
+        assert!(matches!(err, TrackerError::Field(FieldError::InvalidValue { field, .. }) if field == "peers"));
+
+        let err = TrackerResponse::from_bytes(b"d8:intervali60e6:peers65:abcdee".to_vec()).unwrap_err();
+        assert!(matches!(err, TrackerError::Field(FieldError::InvalidValue { field, .. }) if field == "peers6"));
+
+        let err = TrackerResponse::from_bytes(b"d8:intervali60e5:peersld2:ip7:1.2.3.4eee".to_vec()).unwrap_err();
+        assert!(matches!(err, TrackerError::Field(FieldError::MissingField(field)) if field == "peers[0].port"));
+
+        let err = TrackerResponse::from_bytes(b"d8:intervali60e5:peersi1ee".to_vec()).unwrap_err();
+        assert!(matches!(err, TrackerError::Field(FieldError::InvalidType { field, .. }) if field == "peers"));
+
+        let err = TrackerResponse::from_bytes(b"l".to_vec()).unwrap_err();
+        assert!(matches!(err, TrackerError::Bdecode(_)));
+    }
+}
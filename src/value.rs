@@ -0,0 +1,338 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    io::Write,
+};
+
+use crate::{BdecodeError, BdecodeNode, BdecodeResult, BencodeResult, BencodeWriter};
+
+/// 可修改的 bencoding 值
+///
+/// 与只读的 BdecodeNode 不同，BencodeValue 持有自己的数据，可以在内存中构建或修改后重新编码。
+/// Dict 使用按 key 字节序排序的 BTreeMap 存放，所以编码结果总是规范（canonical）的。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BencodeValue {
+    Int(i64),
+    Bytes(Vec<u8>),
+    List(Vec<BencodeValue>),
+    Dict(BTreeMap<Vec<u8>, BencodeValue>),
+}
+
+impl BencodeValue {
+    /// 解析 buffer 并转换为 BencodeValue
+    pub fn parse_buffer(buffer: Vec<u8>) -> BdecodeResult<Self> {
+        let node = BdecodeNode::parse_buffer(buffer)?;
+
+        Self::try_from(&node)
+    }
+
+    /// 创建一个空的 list
+    pub fn new_list() -> Self {
+        BencodeValue::List(Vec::new())
+    }
+
+    /// 创建一个空的 dict
+    pub fn new_dict() -> Self {
+        BencodeValue::Dict(BTreeMap::new())
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            BencodeValue::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            BencodeValue::Bytes(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// 获取 utf-8 字符串，如果不是 Bytes 或者不是合法的 utf-8 则返回 None
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|v| std::str::from_utf8(v).ok())
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<BencodeValue>> {
+        match self {
+            BencodeValue::List(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<BencodeValue>> {
+        match self {
+            BencodeValue::List(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&BTreeMap<Vec<u8>, BencodeValue>> {
+        match self {
+            BencodeValue::Dict(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict_mut(&mut self) -> Option<&mut BTreeMap<Vec<u8>, BencodeValue>> {
+        match self {
+            BencodeValue::Dict(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// 获取 list 或 dict 的长度
+    pub fn len(&self) -> usize {
+        match self {
+            BencodeValue::List(v) => v.len(),
+            BencodeValue::Dict(v) => v.len(),
+            _ => panic!("not a List or Dict value"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 在 dict 中查找 key 对应的 value
+    pub fn get(&self, key: &[u8]) -> Option<&BencodeValue> {
+        self.as_dict()?.get(key)
+    }
+
+    /// 在 dict 中查找 key 对应的 value 的可变引用
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut BencodeValue> {
+        self.as_dict_mut()?.get_mut(key)
+    }
+
+    /// 向 dict 中插入键值对，返回 key 原来对应的 value
+    pub fn insert(
+        &mut self,
+        key: impl Into<Vec<u8>>,
+        value: impl Into<BencodeValue>,
+    ) -> Option<BencodeValue> {
+        let BencodeValue::Dict(dict) = self else {
+            panic!("not a Dict value")
+        };
+
+        dict.insert(key.into(), value.into())
+    }
+
+    /// 从 dict 中移除 key 及其对应的 value
+    pub fn remove(&mut self, key: &[u8]) -> Option<BencodeValue> {
+        self.as_dict_mut()?.remove(key)
+    }
+
+    /// 获取 list 中指定索引的值
+    pub fn list_item(&self, index: usize) -> Option<&BencodeValue> {
+        self.as_list()?.get(index)
+    }
+
+    /// 获取 list 中指定索引的值的可变引用
+    pub fn list_item_mut(&mut self, index: usize) -> Option<&mut BencodeValue> {
+        self.as_list_mut()?.get_mut(index)
+    }
+
+    /// 向 list 的末尾追加一个值
+    pub fn push(&mut self, value: impl Into<BencodeValue>) {
+        let BencodeValue::List(list) = self else {
+            panic!("not a List value")
+        };
+
+        list.push(value.into());
+    }
+
+    /// 使用 BencodeWriter 编码当前值
+    pub fn write_to<W: Write>(&self, writer: &mut BencodeWriter<W>) -> BencodeResult<()> {
+        match self {
+            BencodeValue::Int(v) => {
+                writer.int(*v)?;
+            }
+            BencodeValue::Bytes(v) => {
+                writer.bytes(v)?;
+            }
+            BencodeValue::List(list) => {
+                writer.begin_list()?;
+                for item in list {
+                    item.write_to(writer)?;
+                }
+                writer.end()?;
+            }
+            BencodeValue::Dict(dict) => {
+                writer.begin_dict()?;
+                for (key, val) in dict {
+                    writer.bytes(key)?;
+                    val.write_to(writer)?;
+                }
+                writer.end()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 编码到任意 writer 中
+    pub fn encode_to<W: Write>(&self, writer: W) -> BencodeResult<W> {
+        let mut writer = BencodeWriter::new(writer);
+        self.write_to(&mut writer)?;
+
+        writer.finish()
+    }
+
+    /// 编码为 bencoded 字节串
+    pub fn encode(&self) -> Vec<u8> {
+        self.encode_to(Vec::new())
+            .expect("encode to Vec<u8> failed")
+    }
+}
+
+/// 整数超出 i64 的范围时返回 Overflow 错误
+impl<B: AsRef<[u8]>> TryFrom<&BdecodeNode<B>> for BencodeValue {
+    type Error = BdecodeError;
+
+    fn try_from(node: &BdecodeNode<B>) -> BdecodeResult<Self> {
+        let value = match node {
+            BdecodeNode::Int(inner_node) => BencodeValue::Int(inner_node.value()?),
+            BdecodeNode::Str(inner_node) => BencodeValue::Bytes(inner_node.value().into_owned()),
+            BdecodeNode::List(inner_node) => {
+                let list = (0..inner_node.len())
+                    .map(|i| BencodeValue::try_from(&inner_node.item(i)))
+                    .collect::<BdecodeResult<_>>()?;
+
+                BencodeValue::List(list)
+            }
+            BdecodeNode::Dict(inner_node) => {
+                let mut dict = BTreeMap::new();
+                for i in 0..inner_node.len() {
+                    let (key, val) = inner_node.item(i);
+                    let key = key.as_str().into_owned();
+                    // 与 Dict::find 保持一致，重复的 key 以第一个为准
+                    if let Entry::Vacant(entry) = dict.entry(key) {
+                        entry.insert(BencodeValue::try_from(&val)?);
+                    }
+                }
+
+                BencodeValue::Dict(dict)
+            }
+            BdecodeNode::End(_) => Err(BdecodeError::TypeMismatch {
+                expected: "value",
+                found: node.type_name(),
+                offset: node.offset(),
+            })?,
+        };
+
+        Ok(value)
+    }
+}
+
+impl From<i64> for BencodeValue {
+    fn from(v: i64) -> Self {
+        BencodeValue::Int(v)
+    }
+}
+
+impl From<Vec<u8>> for BencodeValue {
+    fn from(v: Vec<u8>) -> Self {
+        BencodeValue::Bytes(v)
+    }
+}
+
+impl From<&[u8]> for BencodeValue {
+    fn from(v: &[u8]) -> Self {
+        BencodeValue::Bytes(v.to_vec())
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for BencodeValue {
+    fn from(v: &[u8; N]) -> Self {
+        BencodeValue::Bytes(v.to_vec())
+    }
+}
+
+impl From<String> for BencodeValue {
+    fn from(v: String) -> Self {
+        BencodeValue::Bytes(v.into_bytes())
+    }
+}
+
+impl From<&str> for BencodeValue {
+    fn from(v: &str) -> Self {
+        BencodeValue::Bytes(v.as_bytes().to_vec())
+    }
+}
+
+impl From<Vec<BencodeValue>> for BencodeValue {
+    fn from(v: Vec<BencodeValue>) -> Self {
+        BencodeValue::List(v)
+    }
+}
+
+impl From<BTreeMap<Vec<u8>, BencodeValue>> for BencodeValue {
+    fn from(v: BTreeMap<Vec<u8>, BencodeValue>) -> Self {
+        BencodeValue::Dict(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_node() {
+        // {"k1": "v1", "k2": [1, -2], "k3": {"k4": 4}}
+        let buffer = "d 2:k1 2:v1 2:k2 l i1e i-2e e 2:k3 d 2:k4 i4e e e".replace(" ", "");
+        let value = BencodeValue::parse_buffer(buffer.clone().into()).unwrap();
+
+        assert_eq!(3, value.len());
+        assert_eq!(Some("v1"), value.get(b"k1").and_then(|v| v.as_str()));
+        assert_eq!(Some(-2), value.get(b"k2").and_then(|v| v.list_item(1)).and_then(|v| v.as_int()));
+        assert_eq!(Some(4), value.get(b"k3").and_then(|v| v.get(b"k4")).and_then(|v| v.as_int()));
+
+        assert_eq!(buffer.as_bytes(), value.encode().as_slice());
+    }
+
+    #[test]
+    fn test_int_range() {
+        for val in [i64::MIN, i64::MAX, -1, 0] {
+            let buffer = BencodeValue::Int(val).encode();
+            assert_eq!(BencodeValue::Int(val), BencodeValue::parse_buffer(buffer.clone()).unwrap());
+            assert_eq!(val, BdecodeNode::parse_buffer(buffer).unwrap().as_int().unwrap());
+        }
+
+        // 超出 i64 范围的整数可以解析，但转换为整数时返回 Overflow 错误
+        let node = BdecodeNode::parse_buffer(b"li9223372036854775808ee".to_vec()).unwrap();
+        assert!(matches!(node.list_item_as_int(0), Err(BdecodeError::Overflow(_))));
+        assert_eq!("[9223372036854775808]", node.to_json());
+        let err = BencodeValue::try_from(&node).unwrap_err();
+        assert!(matches!(err, BdecodeError::Overflow(s) if s == "9223372036854775808"));
+        let err = BencodeValue::parse_buffer(b"i-9223372036854775809e".to_vec()).unwrap_err();
+        assert!(matches!(err, BdecodeError::Overflow(_)));
+    }
+
+    #[test]
+    fn test_modify() {
+        // {"b": 1, "d": [1]}
+        let buffer = "d 1:b i1e 1:d l i1e e e".replace(" ", "");
+        let mut value = BencodeValue::parse_buffer(buffer.into()).unwrap();
+
+        assert_eq!(Some(BencodeValue::Int(1)), value.insert("b", 2));
+        assert_eq!(None, value.insert(b"a", "v"));
+        value.get_mut(b"d").unwrap().push(2);
+        value.insert("c", BencodeValue::new_dict());
+        value.get_mut(b"c").unwrap().insert("k", vec![BencodeValue::from("x")]);
+        assert!(value.remove(b"e").is_none());
+
+        // 编码结果中 key 按字节序排序
+        assert_eq!(b"d1:a1:v1:bi2e1:cd1:kl1:xee1:dli1ei2eee", value.encode().as_slice());
+
+        assert_eq!(Some(BencodeValue::Int(2)), value.remove(b"b"));
+        assert_eq!(3, value.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "not a Dict value")]
+    fn test_panic_insert() {
+        let mut value = BencodeValue::new_list();
+        value.insert("k", 1);
+    }
+}